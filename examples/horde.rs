@@ -0,0 +1,172 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Fixed tick cost with a large horde, run with
+//! `cargo run --release --example horde -- 100 300 500`
+//!
+//! Prints the mean, p99 and max time of a fixed tick for every enemy count,
+//! measured after a warmup on the first level with a seeded run.
+
+use std::f32::consts::TAU;
+use std::time::{Duration, Instant};
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+
+use mageanoid::ext::FRng;
+use mageanoid::gameplay::archetype::{EnemyArchetypes, EnemyArchetypesHandle};
+use mageanoid::gameplay::enemy::{spawn_enemy, Enemy};
+use mageanoid::gameplay::input::PlayerInput;
+use mageanoid::gameplay::player::Player;
+use mageanoid::gameplay::stats::{PlayerStats, Stat, StatModifier};
+use mageanoid::ldtk::{CollisionMap, LevelBounds};
+use mageanoid::seed::{RunRng, RunSeed, SeedOverride};
+use mageanoid::state::AppState;
+use mageanoid::MageanoidSimPlugins;
+
+const WARMUP_TICKS: usize = 60;
+const MEASURED_TICKS: usize = 600;
+/// enemies spawn in a ring around the player so they reach it during the run
+const MIN_DISTANCE: f32 = 300.0;
+const MAX_DISTANCE: f32 = 1200.0;
+const LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_horde(
+    In(count): In<usize>,
+    mut commands: Commands,
+    archetypes: Res<Assets<EnemyArchetypes>>,
+    archetypes_handle: Res<EnemyArchetypesHandle>,
+    bounds: Res<LevelBounds>,
+    collision_map: Res<CollisionMap>,
+    mut rng: ResMut<RunRng>,
+    mut player_q: Query<(&Transform, &mut PlayerStats), With<Player>>,
+) {
+    let archetypes = archetypes.get(&archetypes_handle.0).unwrap();
+    // the benchmark measures the horde, not the death screen
+    let (player_transform, mut stats) = player_q.single_mut();
+    stats.add_modifier(StatModifier::multiply(Stat::MaxHealth, 1.0e9));
+    let player_pos = player_transform.translation.xy();
+
+    let mut spawned = 0;
+    while spawned < count {
+        let angle = rng.enemy_spawn.next_f32_range(0.0, TAU);
+        let distance = rng.enemy_spawn.next_f32_range(MIN_DISTANCE, MAX_DISTANCE);
+        let pos = player_pos + Vec2::from_angle(angle) * distance;
+        if pos.cmplt(bounds.min).any()
+            || pos.cmpgt(bounds.max).any()
+            || collision_map.is_solid_at(pos)
+        {
+            continue;
+        }
+        spawn_enemy(
+            &mut commands,
+            archetypes,
+            None,
+            &mut rng,
+            pos.extend(0.0),
+            1.0,
+        );
+        spawned += 1;
+    }
+}
+
+fn loaded(app: &App) -> bool {
+    let world = app.world();
+    let archetypes_loaded = world
+        .get_resource::<EnemyArchetypesHandle>()
+        .map(|handle| {
+            world
+                .resource::<Assets<EnemyArchetypes>>()
+                .contains(&handle.0)
+        })
+        .unwrap_or(false);
+    archetypes_loaded && world.contains_resource::<LevelBounds>()
+}
+
+fn run(count: usize) -> Vec<Duration> {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins(MageanoidSimPlugins)
+        .insert_resource(SeedOverride(Some(RunSeed(1))));
+
+    let started = Instant::now();
+    while !loaded(&app) {
+        assert!(started.elapsed() < LOAD_TIMEOUT, "assets did not load");
+        app.update();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::InGame);
+    app.update();
+
+    // exactly one fixed tick per update
+    let timestep = app.world().resource::<Time<Fixed>>().timestep();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep));
+    app.world_mut().run_system_once_with(count, spawn_horde);
+
+    let mut ticks = Vec::with_capacity(MEASURED_TICKS);
+    for tick in 0..WARMUP_TICKS + MEASURED_TICKS {
+        // keep firing in a circle so projectiles hit the horde as well,
+        // and take the first upgrade of every level up
+        let angle = tick as f32 * 0.1;
+        let mut input = app.world_mut().resource_mut::<PlayerInput>();
+        input.fire = Some(Vec2::new(angle.cos(), angle.sin()));
+        input.upgrade = Some(0);
+
+        let started = Instant::now();
+        app.update();
+        if tick >= WARMUP_TICKS {
+            ticks.push(started.elapsed());
+        }
+    }
+
+    let enemies = app
+        .world_mut()
+        .query_filtered::<(), With<Enemy>>()
+        .iter(app.world())
+        .count();
+    println!("{} enemies left after the run", enemies);
+
+    ticks
+}
+
+fn main() {
+    let counts: Vec<usize> = std::env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse().ok())
+        .collect();
+    let counts = if counts.is_empty() {
+        vec![100, 300, 500]
+    } else {
+        counts
+    };
+
+    println!("enemies     mean      p99      max");
+    for count in counts {
+        let mut ticks = run(count);
+        ticks.sort();
+        let mean = ticks.iter().sum::<Duration>() / ticks.len() as u32;
+        let p99 = ticks[ticks.len() * 99 / 100];
+        let max = ticks[ticks.len() - 1];
+        println!("{:>7} {:>8.2?} {:>8.2?} {:>8.2?}", count, mean, p99, max);
+    }
+}
@@ -16,7 +16,9 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use crate::seed::RunSeed;
 use crate::state::AppState;
+use crate::style::text;
 use bevy::prelude::*;
 
 #[derive(Debug, Component)]
@@ -24,7 +26,7 @@ pub struct DeathScreen {
     pub remove_timer: Timer,
 }
 
-fn spawn_death_screen(mut commands: Commands) {
+fn spawn_death_screen(mut commands: Commands, seed: Res<RunSeed>) {
     commands
         .spawn((
             DeathScreen {
@@ -54,6 +56,7 @@ fn spawn_death_screen(mut commands: Commands) {
                     ..default()
                 },
             ));
+            parent.spawn(text(format!("Seed: {}", *seed)));
         });
 }
 
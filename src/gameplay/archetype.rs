@@ -0,0 +1,441 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::io;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::ext::FRng;
+use crate::gameplay::behavior::Behavior;
+use crate::gameplay::collision::Collider;
+
+const ARCHETYPES_PATH: &str = "data/default.enemies.json";
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct EnemyStats {
+    pub health: f32,
+    pub speed: f32,
+    /// distance to the player from which the enemy attacks
+    pub attack_range: f32,
+    /// seconds between two attack steps
+    pub attack_interval: f32,
+    pub damage: f32,
+    /// score for killing the enemy
+    pub score: u32,
+}
+
+/**
+ * How an enemy keeps its distance to other enemies
+ */
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct Crowd {
+    /// distance at which other enemies start pushing
+    pub radius: f32,
+    /// weight of the push away from other enemies
+    pub separation: f32,
+    /// weight of the direction the behavior wants to go
+    pub seek: f32,
+}
+
+impl Default for Crowd {
+    fn default() -> Self {
+        Self {
+            radius: 48.0,
+            separation: 1.5,
+            seek: 1.0,
+        }
+    }
+}
+
+/**
+ * Sprite sheet rows of the enemy states, each row holds the frames facing left followed by the frames facing right
+ */
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct EnemyAnimation {
+    pub frames: usize,
+    pub frame_time: f32,
+    pub hunt_row: usize,
+    pub ready_row: usize,
+    pub attack_row: usize,
+    /// tint while getting ready to attack
+    #[serde(default)]
+    pub telegraph_color: Option<[f32; 4]>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(super) struct SpriteSheetDef {
+    path: String,
+    frame_size: [u32; 2],
+    columns: u32,
+    rows: u32,
+    #[serde(default = "default_scale")]
+    scale: f32,
+    #[serde(default)]
+    color: Option<[f32; 4]>,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+pub fn rgba(color: [f32; 4]) -> Color {
+    Color::srgba(color[0], color[1], color[2], color[3])
+}
+
+#[derive(Deserialize, Debug)]
+struct DeathAnimationDef {
+    sprite: SpriteSheetDef,
+    frame_time: f32,
+}
+
+#[derive(Deserialize, Debug)]
+struct EnemyProjectileDef {
+    sprite: SpriteSheetDef,
+    frame_time: f32,
+    speed: f32,
+    lifetime: f32,
+    #[serde(default = "default_volley")]
+    volley: u32,
+    #[serde(default)]
+    spread: f32,
+    #[serde(default = "default_projectile_radius")]
+    radius: f32,
+}
+
+fn default_projectile_radius() -> f32 {
+    20.0
+}
+
+fn default_volley() -> u32 {
+    1
+}
+
+#[derive(Deserialize, Debug)]
+enum EnemyAttackDef {
+    Melee,
+    Ranged { projectile: EnemyProjectileDef },
+}
+
+#[derive(Deserialize, Debug)]
+struct EnemyPhaseDef {
+    below: f32,
+    #[serde(default = "default_scale")]
+    speed_scale: f32,
+    #[serde(default)]
+    attack_interval: Option<f32>,
+    #[serde(default)]
+    attack_range: Option<f32>,
+    #[serde(default)]
+    color: Option<[f32; 4]>,
+    #[serde(default)]
+    attack: Option<EnemyAttackDef>,
+    #[serde(default)]
+    behavior: Option<Behavior>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EnemySoundsDef {
+    attack: String,
+    hit: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct EnemyArchetypeDef {
+    name: String,
+    weight: f32,
+    sprite: SpriteSheetDef,
+    animation: EnemyAnimation,
+    death: DeathAnimationDef,
+    sounds: EnemySoundsDef,
+    stats: EnemyStats,
+    collider: Collider,
+    #[serde(default)]
+    crowd: Crowd,
+    attack: EnemyAttackDef,
+    behavior: Behavior,
+    #[serde(default)]
+    phases: Vec<EnemyPhaseDef>,
+    #[serde(default)]
+    boss: Option<BossInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpriteSheet {
+    pub texture: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+    pub columns: usize,
+    pub frames: usize,
+    pub scale: f32,
+    pub color: Color,
+}
+
+impl SpriteSheet {
+    pub(super) fn load(def: SpriteSheetDef, label: String, load_context: &mut LoadContext) -> Self {
+        let layout = TextureAtlasLayout::from_grid(
+            UVec2::from_array(def.frame_size),
+            def.columns,
+            def.rows,
+            None,
+            None,
+        );
+        Self {
+            texture: load_context.load(def.path),
+            layout: load_context.add_labeled_asset(label, layout),
+            columns: def.columns as usize,
+            frames: (def.columns * def.rows) as usize,
+            scale: def.scale,
+            color: def.color.map(rgba).unwrap_or(Color::WHITE),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeathAnimation {
+    pub sprite: SpriteSheet,
+    pub frame_time: f32,
+}
+
+/**
+ * Shot fired by ranged enemies, the damage comes from the enemy stats
+ */
+#[derive(Debug, Clone)]
+pub struct EnemyProjectile {
+    pub sprite: SpriteSheet,
+    pub frame_time: f32,
+    pub speed: f32,
+    pub lifetime: f32,
+    /// projectiles per shot
+    pub volley: u32,
+    /// angle between the projectiles of a volley in degrees
+    pub spread: f32,
+    /// hitbox in sprite pixels
+    pub radius: f32,
+}
+
+/**
+ * What happens at the end of a wind up in the `Attack` action
+ */
+#[derive(Debug, Clone)]
+pub enum EnemyAttack {
+    /// swings at the player in range
+    Melee,
+    /// shoots at the player in range
+    Ranged { projectile: EnemyProjectile },
+}
+
+impl EnemyAttack {
+    fn load(def: EnemyAttackDef, label: &str, load_context: &mut LoadContext) -> Self {
+        match def {
+            EnemyAttackDef::Melee => Self::Melee,
+            EnemyAttackDef::Ranged { projectile } => Self::Ranged {
+                projectile: EnemyProjectile {
+                    sprite: SpriteSheet::load(
+                        projectile.sprite,
+                        format!("{}/projectile", label),
+                        load_context,
+                    ),
+                    frame_time: projectile.frame_time,
+                    speed: projectile.speed,
+                    lifetime: projectile.lifetime,
+                    volley: projectile.volley,
+                    spread: projectile.spread,
+                    radius: projectile.radius,
+                },
+            },
+        }
+    }
+}
+
+/**
+ * Takes over once the health drops below a fraction of the max health
+ */
+#[derive(Debug, Clone)]
+pub struct EnemyPhase {
+    pub below: f32,
+    pub speed_scale: f32,
+    pub attack_interval: Option<f32>,
+    pub attack_range: Option<f32>,
+    pub color: Option<Color>,
+    pub attack: Option<EnemyAttack>,
+    pub behavior: Option<Behavior>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BossInfo {
+    /// shown above the boss health bar
+    pub title: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnemySounds {
+    pub attack: Handle<AudioSource>,
+    pub hit: Handle<AudioSource>,
+}
+
+/**
+ * Everything that makes up one kind of enemy
+ */
+#[derive(Debug, Clone)]
+pub struct EnemyArchetype {
+    pub name: String,
+    /// how likely the archetype is picked for a wave group without a fixed enemy
+    pub weight: f32,
+    pub sprite: SpriteSheet,
+    pub animation: EnemyAnimation,
+    pub death: DeathAnimation,
+    pub sounds: EnemySounds,
+    pub stats: EnemyStats,
+    pub collider: Collider,
+    pub crowd: Crowd,
+    pub attack: EnemyAttack,
+    pub behavior: Behavior,
+    /// ordered from the highest to the lowest health threshold
+    pub phases: Vec<EnemyPhase>,
+    pub boss: Option<BossInfo>,
+}
+
+#[derive(Asset, TypePath, Debug)]
+pub struct EnemyArchetypes {
+    pub archetypes: Vec<EnemyArchetype>,
+}
+
+impl EnemyArchetypes {
+    pub fn get(&self, name: &str) -> Option<&EnemyArchetype> {
+        self.archetypes
+            .iter()
+            .find(|archetype| archetype.name == name)
+    }
+
+    pub fn choose(&self, rng: &mut impl FRng) -> Option<&EnemyArchetype> {
+        let total: f32 = self
+            .archetypes
+            .iter()
+            .map(|archetype| archetype.weight.max(0.0))
+            .sum();
+        let mut roll = rng.next_f32() * total;
+        for archetype in self.archetypes.iter() {
+            roll -= archetype.weight.max(0.0);
+            if archetype.weight > 0.0 && roll <= 0.0 {
+                return Some(archetype);
+            }
+        }
+        self.archetypes
+            .iter()
+            .rev()
+            .find(|archetype| archetype.weight > 0.0)
+    }
+}
+
+#[derive(Default)]
+pub struct EnemyArchetypesLoader;
+
+impl AssetLoader for EnemyArchetypesLoader {
+    type Asset = EnemyArchetypes;
+    type Settings = ();
+    type Error = io::Error;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<EnemyArchetypes, io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let defs: Vec<EnemyArchetypeDef> = serde_json::from_slice(&bytes)?;
+
+        let archetypes = defs
+            .into_iter()
+            .map(|def| EnemyArchetype {
+                sprite: SpriteSheet::load(def.sprite, format!("{}/sprite", def.name), load_context),
+                death: DeathAnimation {
+                    sprite: SpriteSheet::load(
+                        def.death.sprite,
+                        format!("{}/death", def.name),
+                        load_context,
+                    ),
+                    frame_time: def.death.frame_time,
+                },
+                sounds: EnemySounds {
+                    attack: load_context.load(def.sounds.attack),
+                    hit: load_context.load(def.sounds.hit),
+                },
+                attack: EnemyAttack::load(def.attack, &def.name, load_context),
+                behavior: def.behavior,
+                phases: {
+                    let mut phases: Vec<EnemyPhase> = def
+                        .phases
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, phase)| EnemyPhase {
+                            below: phase.below,
+                            speed_scale: phase.speed_scale,
+                            attack_interval: phase.attack_interval,
+                            attack_range: phase.attack_range,
+                            color: phase.color.map(rgba),
+                            attack: phase.attack.map(|attack| {
+                                EnemyAttack::load(
+                                    attack,
+                                    &format!("{}/phase{}", def.name, index),
+                                    load_context,
+                                )
+                            }),
+                            behavior: phase.behavior,
+                        })
+                        .collect();
+                    phases.sort_by(|a, b| b.below.total_cmp(&a.below));
+                    phases
+                },
+                boss: def.boss,
+                name: def.name,
+                weight: def.weight,
+                animation: def.animation,
+                stats: def.stats,
+                collider: def.collider,
+                crowd: def.crowd,
+            })
+            .collect();
+
+        Ok(EnemyArchetypes { archetypes })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["enemies.json"]
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct EnemyArchetypesHandle(pub Handle<EnemyArchetypes>);
+
+fn load_archetypes(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(EnemyArchetypesHandle(asset_server.load(ARCHETYPES_PATH)));
+}
+
+pub struct ArchetypePlugin;
+
+impl Plugin for ArchetypePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<EnemyArchetypes>()
+            .init_asset_loader::<EnemyArchetypesLoader>()
+            .add_systems(Startup, load_archetypes);
+    }
+}
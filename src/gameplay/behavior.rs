@@ -0,0 +1,268 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::ext::FRng;
+
+/**
+ * What an agent knows about the world when it decides and steers
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Senses {
+    pub position: Vec3,
+    pub player_position: Vec3,
+    pub player_direction: Vec3,
+    /// health left in [0.0, 1.0]
+    pub health: f32,
+}
+
+impl Senses {
+    fn player_distance(&self) -> f32 {
+        self.position.distance(self.player_position)
+    }
+}
+
+/**
+ * How an agent moves while it is in a state
+ */
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum Action {
+    /// stand still
+    Idle,
+    /// run at the player, or `lead` pixels ahead of where the player is heading
+    Chase {
+        #[serde(default)]
+        lead: f32,
+    },
+    /// approach the player `angle` degrees off the direct line and stop `distance` away
+    Flank { angle: f32, distance: f32 },
+    /// circle the player at `distance`
+    Strafe { distance: f32 },
+    /// stand still and attack when the player is in range
+    Attack,
+    /// run away from the player
+    Flee,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum Condition {
+    PlayerWithin(f32),
+    PlayerBeyond(f32),
+    /// health fraction
+    HealthBelow(f32),
+    /// seconds spent in the current state
+    After(f32),
+}
+
+impl Condition {
+    fn holds(&self, senses: &Senses, time_in_state: f32) -> bool {
+        match *self {
+            Condition::PlayerWithin(distance) => senses.player_distance() <= distance,
+            Condition::PlayerBeyond(distance) => senses.player_distance() > distance,
+            Condition::HealthBelow(fraction) => senses.health < fraction,
+            Condition::After(seconds) => time_in_state >= seconds,
+        }
+    }
+}
+
+fn default_chance() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Debug)]
+struct TransitionDef {
+    to: String,
+    #[serde(default)]
+    when: Vec<Condition>,
+    /// rolled when the state is entered, a failed roll disables the transition until then
+    #[serde(default = "default_chance")]
+    chance: f32,
+}
+
+#[derive(Deserialize, Debug)]
+struct StateDef {
+    name: String,
+    action: Action,
+    #[serde(default)]
+    transitions: Vec<TransitionDef>,
+}
+
+#[derive(Debug, Clone)]
+struct Transition {
+    to: usize,
+    when: Vec<Condition>,
+    chance: f32,
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    action: Action,
+    transitions: Vec<Transition>,
+}
+
+/**
+ * A state machine read from a list of states, agents start in the first one.
+ * The first transition whose conditions all hold is taken, entering a state again restarts it.
+ */
+#[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "Vec<StateDef>")]
+pub struct Behavior {
+    states: Vec<State>,
+}
+
+impl TryFrom<Vec<StateDef>> for Behavior {
+    type Error = String;
+
+    fn try_from(defs: Vec<StateDef>) -> Result<Self, Self::Error> {
+        if defs.is_empty() {
+            return Err("behavior without states".to_string());
+        }
+        let index = |name: &str| {
+            defs.iter()
+                .position(|def| def.name == name)
+                .ok_or_else(|| format!("unknown behavior state {}", name))
+        };
+        let states = defs
+            .iter()
+            .map(|def| {
+                let transitions = def
+                    .transitions
+                    .iter()
+                    .map(|transition| {
+                        Ok(Transition {
+                            to: index(&transition.to)?,
+                            when: transition.when.clone(),
+                            chance: transition.chance,
+                        })
+                    })
+                    .collect::<Result<_, String>>()?;
+                Ok(State {
+                    action: def.action,
+                    transitions,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { states })
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct BehaviorAgent {
+    behavior: Behavior,
+    state: usize,
+    time_in_state: f32,
+    /// chance rolls of the transitions of the current state
+    enabled: Vec<bool>,
+    /// the side to flank or strafe on, 1 or -1
+    side: f32,
+}
+
+impl BehaviorAgent {
+    pub fn new(behavior: Behavior, rng: &mut impl FRng) -> Self {
+        let mut agent = Self {
+            behavior,
+            state: 0,
+            time_in_state: 0.0,
+            enabled: Vec::new(),
+            side: 1.0,
+        };
+        agent.enter(0, rng);
+        agent
+    }
+
+    /**
+     * Swaps the state machine and starts over in its first state
+     */
+    pub fn set_behavior(&mut self, behavior: Behavior, rng: &mut impl FRng) {
+        self.behavior = behavior;
+        self.enter(0, rng);
+    }
+
+    pub fn action(&self) -> Action {
+        self.behavior.states[self.state].action
+    }
+
+    fn enter(&mut self, state: usize, rng: &mut impl FRng) {
+        self.state = state;
+        self.time_in_state = 0.0;
+        self.enabled = self.behavior.states[state]
+            .transitions
+            .iter()
+            .map(|transition| transition.chance >= 1.0 || rng.next_f32() < transition.chance)
+            .collect();
+        self.side = if rng.next_f32() < 0.5 { -1.0 } else { 1.0 };
+    }
+
+    /**
+     * Advances the time in the current state and takes the first transition that fires
+     */
+    pub fn think(&mut self, senses: &Senses, delta: f32, rng: &mut impl FRng) {
+        self.time_in_state += delta;
+        let next = self.behavior.states[self.state]
+            .transitions
+            .iter()
+            .zip(self.enabled.iter())
+            .find(|(transition, enabled)| {
+                **enabled
+                    && transition
+                        .when
+                        .iter()
+                        .all(|condition| condition.holds(senses, self.time_in_state))
+            })
+            .map(|(transition, _)| transition.to);
+        if let Some(next) = next {
+            self.enter(next, rng);
+        }
+    }
+
+    /**
+     * The direction to move in, zero to stand still
+     */
+    pub fn steer(&self, senses: &Senses) -> Vec3 {
+        let to_player = senses.player_position - senses.position;
+        match self.action() {
+            Action::Idle | Action::Attack => Vec3::ZERO,
+            Action::Chase { lead } => (senses.player_position + senses.player_direction * lead
+                - senses.position)
+                .normalize_or_zero(),
+            Action::Flank { angle, distance } => {
+                let from_player = -to_player.normalize_or_zero();
+                let spot = senses.player_position
+                    + Quat::from_rotation_z(self.side * angle.to_radians())
+                        * from_player
+                        * distance;
+                let to_spot = spot - senses.position;
+                if to_spot.length() < 8.0 {
+                    Vec3::ZERO
+                } else {
+                    to_spot.normalize()
+                }
+            }
+            Action::Strafe { distance } => {
+                let radial = to_player.normalize_or_zero();
+                let tangent = Vec3::new(-radial.y, radial.x, 0.0) * self.side;
+                // drift back onto the circle while going around it
+                let correction = (to_player.length() - distance) / distance.max(1.0);
+                (tangent + radial * correction.clamp(-1.0, 1.0)).normalize_or_zero()
+            }
+            Action::Flee => -to_player.normalize_or_zero(),
+        }
+    }
+}
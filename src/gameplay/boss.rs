@@ -0,0 +1,159 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use bevy::prelude::*;
+
+use crate::ext::RandomAround;
+use crate::gameplay::archetype::{EnemyArchetypes, EnemyArchetypesHandle};
+use crate::gameplay::enemy::spawn_enemy;
+use crate::gameplay::enemy_die::EnemyDied;
+use crate::gameplay::player::Player;
+use crate::gameplay::wave::{run_director, SpawnArea, WaveList, WaveListHandle};
+use crate::gameplay::GameplaySet;
+use crate::seed::RunRng;
+use crate::state::ON_ENTER_GAMEPLAY;
+
+#[derive(Component, Debug)]
+pub struct Boss {
+    pub title: String,
+}
+
+#[derive(Event, Debug)]
+pub struct BossSpawned {
+    pub title: String,
+}
+
+#[derive(Event, Debug)]
+pub struct BossDefeated {
+    pub title: String,
+    pub pos: Vec3,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct BossDirector {
+    /// kills of enemies that are not bosses
+    kills: u32,
+    /// kills when the last boss showed up
+    last_spawn_kills: u32,
+    /// title of the boss that is alive
+    title: Option<String>,
+}
+
+fn reset_director(mut director: ResMut<BossDirector>) {
+    *director = BossDirector::default();
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn run_boss_director(
+    mut commands: Commands,
+    mut director: ResMut<BossDirector>,
+    wave_lists: Res<Assets<WaveList>>,
+    wave_list_handle: Res<WaveListHandle>,
+    archetypes: Res<Assets<EnemyArchetypes>>,
+    archetypes_handle: Res<EnemyArchetypesHandle>,
+    boss_q: Query<(), With<Boss>>,
+    player_q: Query<&Transform, With<Player>>,
+    spawn_area: SpawnArea,
+    mut rng: ResMut<RunRng>,
+    mut evr_enemy_died: EventReader<EnemyDied>,
+    mut evw_boss_spawned: EventWriter<BossSpawned>,
+    mut evw_boss_defeated: EventWriter<BossDefeated>,
+) {
+    for ev in evr_enemy_died.read() {
+        if ev.boss {
+            evw_boss_defeated.send(BossDefeated {
+                title: director.title.take().unwrap_or_default(),
+                pos: ev.pos,
+            });
+        } else {
+            director.kills += 1;
+        }
+    }
+
+    let schedule = match wave_lists
+        .get(&wave_list_handle.0)
+        .and_then(|wave_list| wave_list.boss.as_ref())
+    {
+        Some(schedule) => schedule,
+        None => return,
+    };
+    let archetypes = match archetypes.get(&archetypes_handle.0) {
+        Some(archetypes) => archetypes,
+        None => return,
+    };
+    let player_pos = match player_q.get_single() {
+        Ok(player_transform) => player_transform.translation,
+        Err(_) => return,
+    };
+
+    // one boss at a time
+    if director.kills < director.last_spawn_kills + schedule.every_kills || !boss_q.is_empty() {
+        return;
+    }
+    let title = match archetypes.get(&schedule.enemy) {
+        Some(archetype) => archetype
+            .boss
+            .as_ref()
+            .map(|boss| boss.title.clone())
+            .unwrap_or_else(|| archetype.name.clone()),
+        None => {
+            warn!("unknown boss {}", schedule.enemy);
+            return;
+        }
+    };
+
+    let translation = match spawn_area.find(&mut rng, |rng, _| {
+        player_pos.random_around(
+            &mut rng.enemy_spawn,
+            schedule.min_radius,
+            schedule.max_radius,
+        )
+    }) {
+        Some(translation) => translation,
+        // nowhere to go right now, try again next tick
+        None => return,
+    };
+    spawn_enemy(
+        &mut commands,
+        archetypes,
+        Some(&schedule.enemy),
+        &mut rng,
+        translation,
+        1.0,
+    );
+    director.last_spawn_kills = director.kills;
+    director.title = Some(title.clone());
+    evw_boss_spawned.send(BossSpawned { title });
+}
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BossDirector>()
+            .add_event::<BossSpawned>()
+            .add_event::<BossDefeated>()
+            .add_systems(ON_ENTER_GAMEPLAY, reset_director)
+            .add_systems(
+                FixedUpdate,
+                run_boss_director
+                    .after(run_director)
+                    .in_set(GameplaySet::Steering),
+            );
+    }
+}
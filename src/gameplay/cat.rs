@@ -18,9 +18,12 @@
 
 use bevy::prelude::*;
 
+use crate::ext::IntoVec3;
 use crate::gameplay::anim::*;
 use crate::gameplay::movement::*;
 use crate::gameplay::player::*;
+use crate::gameplay::GameplaySet;
+use crate::ldtk::SpawnPoints;
 use crate::state::{AppState, ON_ENTER_GAMEPLAY, ON_EXIT_GAMEPLAY};
 
 const CAT_SPEED: f32 = 110.0;
@@ -35,6 +38,7 @@ fn spawn_cat(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    spawn_points: Res<SpawnPoints>,
 ) {
     let texture = asset_server.load("sprites/cat_walk.png");
     let layout = TextureAtlasLayout::from_grid(UVec2::new(64, 64), 4, 1, None, None);
@@ -47,11 +51,8 @@ fn spawn_cat(
         AnimatedSpriteBundle {
             sprite: SpriteBundle {
                 texture,
-                transform: Transform::from_scale(Vec3::splat(1.0)).with_translation(Vec3::new(
-                    -CAT_SPEED / 2.0,
-                    CAT_SPEED / 2.0,
-                    0.0,
-                )),
+                transform: Transform::from_scale(Vec3::splat(1.0))
+                    .with_translation(spawn_points.cat.xyz()),
                 ..default()
             },
             atlas: TextureAtlas {
@@ -63,7 +64,9 @@ fn spawn_cat(
         },
         MovingObjectBundle {
             velocity: Velocity::from_vec3(Vec3::new(0.0, 0.0, 0.0), CAT_SPEED),
+            ..default()
         },
+        TileCollider::default(),
     ));
 }
 
@@ -110,10 +113,8 @@ fn despawn_cat(mut commands: Commands, cat_q: Query<Entity, With<Cat>>) {
 impl Plugin for CatPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(ON_ENTER_GAMEPLAY, spawn_cat)
-            .add_systems(
-                Update,
-                (update_position, update_animation).run_if(in_state(AppState::InGame)),
-            )
+            .add_systems(FixedUpdate, update_position.in_set(GameplaySet::Steering))
+            .add_systems(Update, update_animation.run_if(in_state(AppState::InGame)))
             .add_systems(ON_EXIT_GAMEPLAY, despawn_cat);
     }
 }
@@ -0,0 +1,266 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::gameplay::enemy::Enemy;
+use crate::gameplay::player::Player;
+use crate::gameplay::projectile::{projectile_out_of_bounds, Projectile, ProjectileOwner};
+use crate::gameplay::spatial::SpatialIndex;
+use crate::gameplay::GameplaySet;
+
+/// how far indexed things can move between building the spatial index and using it
+pub(super) const MOVEMENT_MARGIN: f32 = 32.0;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ColliderShape {
+    Circle { radius: f32 },
+    Aabb { half_size: [f32; 2] },
+}
+
+/**
+ * Hitbox in sprite pixels, scaled along with the sprite
+ */
+#[derive(Component, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Collider {
+    pub shape: ColliderShape,
+    /// from the center of the sprite
+    #[serde(default)]
+    pub offset: [f32; 2],
+}
+
+impl Collider {
+    pub fn circle(radius: f32) -> Self {
+        Self {
+            shape: ColliderShape::Circle { radius },
+            offset: [0.0, 0.0],
+        }
+    }
+
+    pub fn aabb(half_width: f32, half_height: f32) -> Self {
+        Self {
+            shape: ColliderShape::Aabb {
+                half_size: [half_width, half_height],
+            },
+            offset: [0.0, 0.0],
+        }
+    }
+
+    fn world(&self, transform: &Transform) -> (Vec2, ColliderShape) {
+        let scale = transform.scale.xy().abs();
+        let center = transform.translation.xy() + Vec2::from_array(self.offset) * scale;
+        let shape = match self.shape {
+            ColliderShape::Circle { radius } => ColliderShape::Circle {
+                radius: radius * scale.max_element(),
+            },
+            ColliderShape::Aabb { half_size } => ColliderShape::Aabb {
+                half_size: (Vec2::from_array(half_size) * scale).to_array(),
+            },
+        };
+        (center, shape)
+    }
+
+    /**
+     * Radius around the transform that contains the whole collider
+     */
+    pub fn extent(&self, transform: &Transform) -> f32 {
+        let (center, shape) = self.world(transform);
+        let offset = center.distance(transform.translation.xy());
+        match shape {
+            ColliderShape::Circle { radius } => offset + radius,
+            ColliderShape::Aabb { half_size } => offset + Vec2::from_array(half_size).length(),
+        }
+    }
+
+    pub fn overlaps(
+        &self,
+        transform: &Transform,
+        other: &Collider,
+        other_transform: &Transform,
+    ) -> bool {
+        let (a, a_shape) = self.world(transform);
+        let (b, b_shape) = other.world(other_transform);
+        match (a_shape, b_shape) {
+            (
+                ColliderShape::Circle { radius: a_radius },
+                ColliderShape::Circle { radius: b_radius },
+            ) => a.distance_squared(b) <= (a_radius + b_radius) * (a_radius + b_radius),
+            (
+                ColliderShape::Aabb { half_size: a_half },
+                ColliderShape::Aabb { half_size: b_half },
+            ) => {
+                let gap = (a - b).abs() - Vec2::from_array(a_half) - Vec2::from_array(b_half);
+                gap.x <= 0.0 && gap.y <= 0.0
+            }
+            (ColliderShape::Circle { radius }, ColliderShape::Aabb { half_size }) => {
+                circle_overlaps_aabb(a, radius, b, Vec2::from_array(half_size))
+            }
+            (ColliderShape::Aabb { half_size }, ColliderShape::Circle { radius }) => {
+                circle_overlaps_aabb(b, radius, a, Vec2::from_array(half_size))
+            }
+        }
+    }
+}
+
+fn circle_overlaps_aabb(circle: Vec2, radius: f32, aabb: Vec2, half_size: Vec2) -> bool {
+    let closest = circle.clamp(aabb - half_size, aabb + half_size);
+    closest.distance_squared(circle) <= radius * radius
+}
+
+/**
+ * Overlaps found by the collision pass, in a stable order
+ */
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionEvent {
+    ProjectileHitEnemy {
+        projectile: Entity,
+        enemy: Entity,
+    },
+    ProjectileHitPlayer {
+        projectile: Entity,
+    },
+    /// the attack range of a melee enemy reaches the player
+    EnemyMeleeHitPlayer {
+        enemy: Entity,
+    },
+}
+
+pub(super) fn detect_collisions(
+    projectile_q: Query<(Entity, &Projectile, &Collider, &Transform)>,
+    enemy_q: Query<(&Enemy, &Collider, &Transform)>,
+    player_q: Query<(&Collider, &Transform), With<Player>>,
+    index: Res<SpatialIndex>,
+    mut evw_collision: EventWriter<CollisionEvent>,
+) {
+    let player = player_q.get_single().ok();
+    for (projectile, owner, collider, transform) in projectile_q.iter() {
+        match owner.owner {
+            ProjectileOwner::Player => {
+                let pos = transform.translation;
+                let reach = collider.extent(transform) + index.enemies.extent() + MOVEMENT_MARGIN;
+                for (enemy, _) in index.enemies.query(pos, reach) {
+                    let (_, enemy_collider, enemy_transform) = match enemy_q.get(enemy) {
+                        Ok(enemy) => enemy,
+                        Err(_) => continue,
+                    };
+                    if collider.overlaps(transform, enemy_collider, enemy_transform) {
+                        evw_collision
+                            .send(CollisionEvent::ProjectileHitEnemy { projectile, enemy });
+                    }
+                }
+            }
+            ProjectileOwner::Enemy => {
+                if let Some((player_collider, player_transform)) = player {
+                    if collider.overlaps(transform, player_collider, player_transform) {
+                        evw_collision.send(CollisionEvent::ProjectileHitPlayer { projectile });
+                    }
+                }
+            }
+        }
+    }
+
+    let (player_collider, player_transform) = match player {
+        Some(player) => player,
+        None => return,
+    };
+    let pos = player_transform.translation;
+    let reach = player_collider.extent(player_transform) + index.enemy_reach + MOVEMENT_MARGIN;
+    for (enemy, _) in index.enemies.query(pos, reach) {
+        let (attacker, _, enemy_transform) = match enemy_q.get(enemy) {
+            Ok(enemy) => enemy,
+            Err(_) => continue,
+        };
+        let range = match attacker.melee_range() {
+            Some(range) => range,
+            None => continue,
+        };
+        // the reach is centered on the enemy and doesn't grow with its sprite
+        let attack = Collider::circle(range);
+        let attack_transform = Transform::from_translation(enemy_transform.translation);
+        if attack.overlaps(&attack_transform, player_collider, player_transform) {
+            evw_collision.send(CollisionEvent::EnemyMeleeHitPlayer { enemy });
+        }
+    }
+}
+
+/**
+ * Draws all colliders on top of the sprites, toggled with F3
+ */
+#[derive(Resource, Debug, Default, PartialEq)]
+pub struct ColliderOverlay(pub bool);
+
+fn toggle_collider_overlay(keys: Res<ButtonInput<KeyCode>>, mut overlay: ResMut<ColliderOverlay>) {
+    if keys.just_pressed(KeyCode::F3) {
+        overlay.0 = !overlay.0;
+    }
+}
+
+fn draw_colliders(
+    mut gizmos: Gizmos,
+    collider_q: Query<(&Collider, &Transform, Has<Enemy>, Has<Player>)>,
+) {
+    for (collider, transform, enemy, player) in collider_q.iter() {
+        let color = if player {
+            Color::srgb(0.2, 1.0, 0.4)
+        } else if enemy {
+            Color::srgb(1.0, 0.3, 0.3)
+        } else {
+            Color::srgb(1.0, 0.9, 0.2)
+        };
+        match collider.world(transform) {
+            (center, ColliderShape::Circle { radius }) => {
+                gizmos.circle_2d(center, radius, color);
+            }
+            (center, ColliderShape::Aabb { half_size }) => {
+                gizmos.rect_2d(center, 0.0, Vec2::from_array(half_size) * 2.0, color);
+            }
+        }
+    }
+}
+
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CollisionEvent>().add_systems(
+            FixedUpdate,
+            detect_collisions
+                .after(projectile_out_of_bounds)
+                .in_set(GameplaySet::Combat),
+        );
+    }
+}
+
+/**
+ * Collider overlay for debugging hitboxes, needs the gizmos of the renderer
+ */
+pub struct ColliderOverlayPlugin;
+
+impl Plugin for ColliderOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ColliderOverlay>().add_systems(
+            Update,
+            (
+                toggle_collider_overlay,
+                draw_colliders.run_if(resource_equals(ColliderOverlay(true))),
+            )
+                .chain(),
+        );
+    }
+}
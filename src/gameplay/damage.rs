@@ -0,0 +1,265 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use bevy::prelude::*;
+
+use crate::gameplay::boss::Boss;
+use crate::gameplay::health::{Health, Invulnerable};
+use crate::gameplay::movement::Velocity;
+use crate::gameplay::overlap::StaticObject;
+use crate::gameplay::player::Player;
+use crate::gameplay::GameplaySet;
+use crate::state::{AppState, ON_EXIT_GAMEPLAY};
+
+/// pixels per second a hit pushes its target away, fading out while it moves
+const KNOCKBACK_SPEED: f32 = 350.0;
+const PLAYER_HIT_INVULNERABILITY: f32 = 0.8;
+const HIT_FLASH_TIME: f32 = 0.12;
+const HIT_FLASH_COLOR: Color = Color::srgb(1.0, 0.25, 0.25);
+const DAMAGE_NUMBER_TIME: f32 = 0.7;
+/// pixels per second the damage numbers float up
+const DAMAGE_NUMBER_SPEED: f32 = 40.0;
+
+/**
+ * Sent whenever something hits the player or an enemy,
+ * the player takes no damage while it is invulnerable
+ */
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    /// where the target was hit
+    pub position: Vec3,
+    /// where the hit came from, the target is pushed away from it
+    pub source: Vec3,
+}
+
+/**
+ * Sent for the hits that took health, drives the hit flash, the damage numbers and deaths
+ */
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageTaken {
+    pub target: Entity,
+    pub amount: f32,
+    pub position: Vec3,
+    /// the hit took the last of the health
+    pub killed: bool,
+}
+
+/**
+ * Tints the sprite for a moment after a hit. Everything that can be hit carries one
+ * from its spawn on, so a hit doesn't change its archetype outside of the fixed tick.
+ */
+#[derive(Component, Debug, Default)]
+pub struct HitFlash {
+    timer: Option<Timer>,
+    /// the color before the flash
+    color: Color,
+}
+
+#[derive(Component, Debug)]
+struct DamageNumber {
+    timer: Timer,
+}
+
+/**
+ * The only place health is taken, the first hit on the player makes it invulnerable
+ * right away so further hits of the same tick are ignored
+ */
+#[allow(clippy::type_complexity)]
+pub(super) fn apply_damage(
+    mut commands: Commands,
+    mut evr_damage: EventReader<DamageEvent>,
+    mut target_q: Query<(
+        &mut Velocity,
+        Has<Boss>,
+        Option<&mut Health>,
+        Option<&mut Invulnerable>,
+        Has<Player>,
+    )>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut evw_damage_taken: EventWriter<DamageTaken>,
+) {
+    // the player was hit this tick, the Invulnerable insert is still pending
+    let mut player_hit = false;
+    for ev in evr_damage.read() {
+        let (mut velocity, boss, health, invulnerable, player) = match target_q.get_mut(ev.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+
+        let mut health = match health {
+            Some(health) => health,
+            None => continue,
+        };
+        // killed by an earlier hit of this tick, the despawn is still pending
+        if health.health <= 0.0 {
+            continue;
+        }
+
+        if player {
+            if player_hit || invulnerable.is_some() {
+                continue;
+            }
+            player_hit = true;
+            commands
+                .entity(ev.target)
+                .insert(Invulnerable::new(PLAYER_HIT_INVULNERABILITY));
+        }
+        let killed = health.damage(ev.amount);
+        if player && killed {
+            next_state.set(AppState::Death);
+        }
+
+        // bosses don't get pushed around
+        if !boss {
+            velocity.impulse = (ev.position - ev.source)
+                .truncate()
+                .normalize_or_zero()
+                .extend(0.0)
+                * KNOCKBACK_SPEED;
+        }
+        evw_damage_taken.send(DamageTaken {
+            target: ev.target,
+            amount: ev.amount,
+            position: ev.position,
+            killed,
+        });
+    }
+}
+
+fn start_hit_flash(
+    mut evr_damage_taken: EventReader<DamageTaken>,
+    mut flash_q: Query<(&mut HitFlash, &Sprite)>,
+) {
+    for ev in evr_damage_taken.read() {
+        let (mut flash, sprite) = match flash_q.get_mut(ev.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        // keep the color from before the first of overlapping flashes
+        if flash.timer.is_none() {
+            flash.color = sprite.color;
+        }
+        flash.timer = Some(Timer::from_seconds(HIT_FLASH_TIME, TimerMode::Once));
+    }
+}
+
+/**
+ * Runs after the animation systems picked the sprite colors of the frame
+ */
+fn update_hit_flash(mut flash_q: Query<(&mut HitFlash, &mut Sprite)>, time: Res<Time>) {
+    for (mut flash, mut sprite) in flash_q.iter_mut() {
+        let timer = match flash.timer.as_mut() {
+            Some(timer) => timer,
+            None => continue,
+        };
+        if timer.tick(time.delta()).finished() {
+            flash.timer = None;
+            sprite.color = flash.color;
+            continue;
+        }
+        sprite.color = HIT_FLASH_COLOR;
+    }
+}
+
+fn spawn_damage_numbers(
+    mut commands: Commands,
+    mut evr_damage_taken: EventReader<DamageTaken>,
+    player_q: Query<(), With<Player>>,
+) {
+    for ev in evr_damage_taken.read() {
+        let color = if player_q.contains(ev.target) {
+            Color::srgb(1.0, 0.3, 0.3)
+        } else {
+            Color::WHITE
+        };
+        let amount = if ev.amount.fract() == 0.0 {
+            format!("{:.0}", ev.amount)
+        } else {
+            format!("{:.1}", ev.amount)
+        };
+
+        commands.spawn((
+            StaticObject,
+            DamageNumber {
+                timer: Timer::from_seconds(DAMAGE_NUMBER_TIME, TimerMode::Once),
+            },
+            Text2dBundle {
+                text: Text::from_section(
+                    amount,
+                    TextStyle {
+                        font_size: 20.0,
+                        color,
+                        ..default()
+                    },
+                ),
+                // above the head and everything else
+                transform: Transform::from_translation(
+                    ev.position.truncate().extend(10.0) + Vec3::Y * 32.0,
+                ),
+                ..default()
+            },
+        ));
+    }
+}
+
+fn update_damage_numbers(
+    mut commands: Commands,
+    mut number_q: Query<(Entity, &mut DamageNumber, &mut Transform, &mut Text)>,
+    time: Res<Time>,
+) {
+    for (entity, mut number, mut transform, mut text) in number_q.iter_mut() {
+        if number.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation.y += DAMAGE_NUMBER_SPEED * time.delta_seconds();
+        for section in text.sections.iter_mut() {
+            section
+                .style
+                .color
+                .set_alpha(number.timer.fraction_remaining());
+        }
+    }
+}
+
+fn despawn_damage_numbers(mut commands: Commands, number_q: Query<Entity, With<DamageNumber>>) {
+    for entity in number_q.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+pub struct DamagePlugin;
+
+impl Plugin for DamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<DamageTaken>()
+            .add_systems(FixedUpdate, apply_damage.in_set(GameplaySet::Combat))
+            .add_systems(
+                Update,
+                (start_hit_flash, spawn_damage_numbers, update_damage_numbers)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                PostUpdate,
+                update_hit_flash.run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(ON_EXIT_GAMEPLAY, despawn_damage_numbers);
+    }
+}
@@ -0,0 +1,187 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use bevy::audio::PlaybackMode;
+use bevy::prelude::*;
+
+use crate::gameplay::anim::AnimationIndices;
+use crate::gameplay::health::{tick_invulnerability, Invulnerable};
+use crate::gameplay::input::PlayerInput;
+use crate::gameplay::movement::Velocity;
+use crate::gameplay::overlap::StaticObject;
+use crate::gameplay::player::{player_movement, Player};
+use crate::gameplay::spell::SpellFx;
+use crate::gameplay::GameplaySet;
+use crate::persistent::Mixer;
+
+const DODGE_SPEED: f32 = 650.0;
+const DODGE_TIME: f32 = 0.2;
+const DODGE_COOLDOWN: f32 = 1.0;
+/// a bit longer than the roll itself so landing next to an enemy is safe
+const DODGE_INVULNERABILITY: f32 = 0.3;
+const AFTERIMAGE_INTERVAL: f32 = 0.04;
+const AFTERIMAGE_TIME: f32 = 0.25;
+
+#[derive(Component, Debug)]
+pub struct Dodge {
+    pub cooldown: Timer,
+    /// running while the player rolls
+    roll: Timer,
+    direction: Vec3,
+    afterimage: Timer,
+}
+
+impl Default for Dodge {
+    fn default() -> Self {
+        let mut cooldown = Timer::from_seconds(DODGE_COOLDOWN, TimerMode::Once);
+        cooldown.set_elapsed(cooldown.duration());
+        let mut roll = Timer::from_seconds(DODGE_TIME, TimerMode::Once);
+        roll.set_elapsed(roll.duration());
+
+        Self {
+            cooldown,
+            roll,
+            direction: Vec3::ZERO,
+            afterimage: Timer::from_seconds(AFTERIMAGE_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+impl Dodge {
+    pub fn rolling(&self) -> bool {
+        !self.roll.finished()
+    }
+}
+
+fn dodge_sound_fx(commands: &mut Commands, asset_server: &AssetServer, mixer: &Mixer) {
+    commands.spawn(AudioBundle {
+        source: asset_server.load("sounds/56_Attack_03.wav"),
+        settings: PlaybackSettings {
+            mode: PlaybackMode::Despawn,
+            volume: mixer.as_volume_with_multiplier(0.6),
+            speed: 1.4,
+            ..default()
+        },
+    });
+}
+
+fn spawn_afterimage(
+    commands: &mut Commands,
+    texture: &Handle<Image>,
+    atlas: &TextureAtlas,
+    transform: &Transform,
+) {
+    commands.spawn((
+        StaticObject,
+        SpellFx {
+            timer: Timer::from_seconds(AFTERIMAGE_TIME, TimerMode::Once),
+        },
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::srgba(0.6, 0.7, 1.0, 0.6),
+                ..default()
+            },
+            texture: texture.clone(),
+            // behind the player
+            transform: transform.with_translation(transform.translation - Vec3::Z * 0.0005),
+            ..default()
+        },
+        atlas.clone(),
+    ));
+}
+
+/**
+ * Rolls in the input direction, or the facing direction without one,
+ * overriding the walking speed until the roll is over
+ */
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn dodge(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut player_q: Query<
+        (
+            Entity,
+            &mut Dodge,
+            &mut Velocity,
+            Option<&Invulnerable>,
+            &AnimationIndices,
+            &Handle<Image>,
+            &TextureAtlas,
+            &Transform,
+        ),
+        With<Player>,
+    >,
+    input: Res<PlayerInput>,
+    time: Res<Time>,
+    #[cfg(not(feature = "storage"))] mixer: Res<Mixer>,
+    #[cfg(feature = "storage")] mixer: Res<bevy_persistent::Persistent<Mixer>>,
+) {
+    for (player, mut dodge, mut velocity, invulnerable, indices, texture, atlas, transform) in
+        player_q.iter_mut()
+    {
+        dodge.cooldown.tick(time.delta());
+        dodge.roll.tick(time.delta());
+
+        if input.dodge && dodge.cooldown.finished() && !dodge.rolling() {
+            dodge.direction = if input.movement.length() > 0.0 {
+                input.movement.normalize().extend(0.0)
+            } else if indices.first == 0 {
+                Vec3::NEG_X
+            } else {
+                Vec3::X
+            };
+            dodge.cooldown.reset();
+            dodge.roll.reset();
+            dodge.afterimage.reset();
+
+            // always a fresh insert, a finished timer may already have its removal queued
+            let seconds = invulnerable.map_or(DODGE_INVULNERABILITY, |invulnerable| {
+                invulnerable
+                    .timer
+                    .remaining_secs()
+                    .max(DODGE_INVULNERABILITY)
+            });
+            commands.entity(player).insert(Invulnerable::new(seconds));
+            dodge_sound_fx(&mut commands, &asset_server, &mixer);
+            spawn_afterimage(&mut commands, texture, atlas, transform);
+        }
+
+        if !dodge.rolling() {
+            continue;
+        }
+        velocity.direction = dodge.direction;
+        velocity.speed = DODGE_SPEED;
+
+        if dodge.afterimage.tick(time.delta()).just_finished() {
+            spawn_afterimage(&mut commands, texture, atlas, transform);
+        }
+    }
+}
+
+pub struct DodgePlugin;
+
+impl Plugin for DodgePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            dodge
+                .after(player_movement)
+                .after(tick_invulnerability)
+                .in_set(GameplaySet::Steering),
+        );
+    }
+}
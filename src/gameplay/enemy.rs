@@ -17,21 +17,26 @@
  */
 use bevy::audio::PlaybackMode;
 use bevy::prelude::*;
-use bevy_prng::WyRand;
-use bevy_rand::prelude::GlobalEntropy;
-use rand_core::RngCore;
+use bevy::utils::HashMap;
 
-use crate::ext::{IntoVec3, RandomAround};
 use crate::gameplay::anim::*;
+use crate::gameplay::archetype::*;
+use crate::gameplay::behavior::{Action, BehaviorAgent, Senses};
+use crate::gameplay::boss::{run_boss_director, Boss};
+use crate::gameplay::collision::{detect_collisions, Collider, CollisionEvent, MOVEMENT_MARGIN};
+use crate::gameplay::damage::{apply_damage, DamageEvent, DamageTaken, HitFlash};
 use crate::gameplay::enemy_die::EnemyDied;
 use crate::gameplay::health::*;
 use crate::gameplay::movement::*;
+use crate::gameplay::navigation::{update_flow_field, FlowField};
 use crate::gameplay::player::*;
 use crate::gameplay::projectile::*;
+use crate::gameplay::spatial::{rebuild_spatial_index, SpatialGrid, SpatialIndex};
+use crate::gameplay::GameplaySet;
+use crate::ldtk::CollisionMap;
 use crate::persistent::{Mixer, Score};
-use crate::state::{AppState, ON_ENTER_GAMEPLAY, ON_EXIT_GAMEPLAY};
-
-const ENEMY_THRESHOLD: f32 = 64.0;
+use crate::seed::RunRng;
+use crate::state::{AppState, ON_EXIT_GAMEPLAY};
 
 #[derive(Debug)]
 enum EnemyState {
@@ -40,13 +45,6 @@ enum EnemyState {
     SwingBlade,
 }
 
-#[derive(Debug)]
-enum EnemyTarget {
-    Player,
-    PlayerFuture,
-    Location(Vec2),
-}
-
 #[derive(Debug)]
 enum Face {
     Left,
@@ -58,153 +56,381 @@ pub struct Enemy {
     animation_state: EnemyState,
     face: Face,
     sword_hit_timer: Timer,
-    target_switch_timer: Timer,
-    target: EnemyTarget,
+    stats: EnemyStats,
+    crowd: Crowd,
+    animation: EnemyAnimation,
+    columns: usize,
+    color: Color,
+    sounds: EnemySounds,
+    death: DeathAnimation,
+    attack: EnemyAttack,
+    phases: Vec<EnemyPhase>,
+    /// number of phases passed
+    phase: usize,
+    speed_scale: f32,
 }
 
-impl Default for Enemy {
-    fn default() -> Self {
+impl Enemy {
+    fn new(archetype: &EnemyArchetype, speed_scale: f32) -> Self {
         Self {
             animation_state: EnemyState::Hunting,
             face: Face::Left,
-            sword_hit_timer: Timer::from_seconds(0.3, TimerMode::Repeating),
-            target_switch_timer: Timer::from_seconds(6.0, TimerMode::Repeating),
-            target: EnemyTarget::Player,
+            sword_hit_timer: Timer::from_seconds(
+                archetype.stats.attack_interval,
+                TimerMode::Repeating,
+            ),
+            stats: archetype.stats,
+            crowd: archetype.crowd,
+            animation: archetype.animation,
+            columns: archetype.sprite.columns,
+            color: archetype.sprite.color,
+            sounds: archetype.sounds.clone(),
+            death: archetype.death.clone(),
+            attack: archetype.attack.clone(),
+            phases: archetype.phases.clone(),
+            phase: 0,
+            speed_scale,
+        }
+    }
+
+    pub fn attack_range(&self) -> f32 {
+        self.stats.attack_range
+    }
+
+    /**
+     * How far the enemy swings at the player, `None` for enemies that shoot
+     */
+    pub fn melee_range(&self) -> Option<f32> {
+        match self.attack {
+            EnemyAttack::Melee => Some(self.stats.attack_range),
+            EnemyAttack::Ranged { .. } => None,
         }
     }
 }
 
-fn spawn_enemy(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
-    player_transform_q: Query<&Transform, With<Player>>,
-    enemy_q: Query<&Enemy>,
-    mut rng: ResMut<GlobalEntropy<WyRand>>,
-    difficulty: Res<EnemyDifficulty>,
+/**
+ * Spawns one enemy, the spawn director decides where, when and how fast.
+ * Without a kind the archetype is picked by weight.
+ */
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    archetypes: &EnemyArchetypes,
+    kind: Option<&str>,
+    rng: &mut RunRng,
+    translation: Vec3,
+    speed_scale: f32,
 ) {
-    let enemies_to_spawn = difficulty.get_enemy_max_count() - enemy_q.iter().count();
-    let player_transform = match player_transform_q.get_single() {
-        Ok(player) => player,
-        Err(_) => return,
+    let archetype = match kind {
+        Some(kind) => archetypes.get(kind),
+        None => archetypes.choose(&mut rng.enemy_spawn),
     };
+    let archetype = match archetype {
+        Some(archetype) => archetype,
+        None => {
+            warn!("unknown enemy {}", kind.unwrap_or("archetype"));
+            return;
+        }
+    };
+
+    let animation_indices = AnimationIndices::new(0, 0);
+
+    let mut enemy = commands.spawn((
+        Enemy::new(archetype, speed_scale),
+        BehaviorAgent::new(archetype.behavior.clone(), &mut rng.enemy_target),
+        Health::new(archetype.stats.health),
+        HitFlash::default(),
+        archetype.collider,
+        SpriteBundle {
+            sprite: Sprite {
+                color: archetype.sprite.color,
+                ..default()
+            },
+            texture: archetype.sprite.texture.clone(),
+            transform: Transform {
+                translation,
+                scale: Vec3::splat(archetype.sprite.scale),
+                ..default()
+            },
+            ..default()
+        },
+        TextureAtlas {
+            layout: archetype.sprite.layout.clone(),
+            index: animation_indices.first,
+        },
+        animation_indices,
+        AnimationTimer(Timer::from_seconds(
+            archetype.animation.frame_time,
+            TimerMode::Repeating,
+        )),
+        MovingObjectBundle {
+            velocity: Velocity::from_vec3(
+                Vec3::new(0.0, 0.0, 0.0),
+                archetype.stats.speed * speed_scale,
+            ),
+            ..default()
+        },
+        TileCollider::default(),
+    ));
+    if let Some(boss) = &archetype.boss {
+        enemy.insert(Boss {
+            title: boss.title.clone(),
+        });
+    }
+}
 
-    for _ in 0..enemies_to_spawn {
-        let texture = asset_server.load("sprites/skeleton.png");
-        let layout = TextureAtlasLayout::from_grid(UVec2::new(64, 64), 4, 3, None, None);
-        let texture_atlas_layout = texture_atlas_layouts.add(layout);
-        let animation_indices = AnimationIndices::new(0, 0);
-
-        commands.spawn((
-            Enemy::default(),
-            Health::new(1.0),
-            SpriteBundle {
-                texture,
+fn spawn_enemy_projectile(
+    commands: &mut Commands,
+    projectile: &EnemyProjectile,
+    damage: f32,
+    position: Vec3,
+    direction: Vec3,
+) {
+    let animation_indices = AnimationIndices::new(0, projectile.sprite.frames.max(1) - 1);
+    commands.spawn(ProjectileBundle {
+        projectile: Projectile::new(ProjectileOwner::Enemy, damage, projectile.lifetime),
+        collider: Collider::circle(projectile.radius),
+        animated_sprite: AnimatedSpriteBundle {
+            sprite: SpriteBundle {
+                sprite: Sprite {
+                    color: projectile.sprite.color,
+                    ..default()
+                },
+                texture: projectile.sprite.texture.clone(),
                 transform: Transform {
-                    translation: player_transform.translation.random_around(
-                        &mut rng,
-                        difficulty.get_enemy_speed() * 15.0,
-                        difficulty.get_enemy_speed() * 30.0,
-                    ),
-                    scale: Vec3::splat(1.0),
+                    translation: position + direction * 32.0,
+                    scale: Vec3::splat(projectile.sprite.scale),
                     ..default()
                 },
                 ..default()
             },
-            TextureAtlas {
-                layout: texture_atlas_layout,
+            atlas: TextureAtlas {
+                layout: projectile.sprite.layout.clone(),
                 index: animation_indices.first,
             },
-            animation_indices,
-            AnimationTimer(Timer::from_seconds(0.25, TimerMode::Repeating)),
-            MovingObjectBundle {
-                velocity: Velocity::from_vec3(
-                    Vec3::new(0.0, 0.0, 0.0),
-                    difficulty.get_enemy_speed(),
-                ),
-            },
-        ));
+            indices: animation_indices,
+            timer: AnimationTimer(Timer::from_seconds(
+                projectile.frame_time,
+                TimerMode::Repeating,
+            )),
+        },
+        moving_object: MovingObjectBundle {
+            velocity: Velocity::from_vec3(direction, projectile.speed),
+            ..default()
+        },
+    });
+}
+
+/**
+ * Swing timers run for every enemy, not only for the ones close enough to attack
+ */
+fn tick_attack_timers(mut enemy_q: Query<(&mut Enemy, &BehaviorAgent)>, time: Res<Time>) {
+    for (mut enemy, agent) in enemy_q.iter_mut() {
+        enemy.sword_hit_timer.tick(time.delta());
+        if !matches!(agent.action(), Action::Attack) {
+            enemy.animation_state = EnemyState::Hunting;
+        }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn enemy_attack(
-    mut enemy_q: Query<(&mut Enemy, &GlobalTransform)>,
-    mut player_q: Query<(&Transform, &mut Health), With<Player>>,
-    time: Res<Time>,
+    mut enemy_q: Query<(&mut Enemy, &BehaviorAgent, &Transform), Without<Player>>,
+    player_q: Query<(Entity, &Collider, &Transform), With<Player>>,
+    index: Res<SpatialIndex>,
+    mut evr_collision: EventReader<CollisionEvent>,
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut next_state: ResMut<NextState<AppState>>,
+    mut evw_damage: EventWriter<DamageEvent>,
     #[cfg(not(feature = "storage"))] mixer: Res<Mixer>,
     #[cfg(feature = "storage")] mixer: Res<bevy_persistent::Persistent<Mixer>>,
 ) {
-    for (mut enemy, enemy_transform) in enemy_q.iter_mut() {
-        enemy.sword_hit_timer.tick(time.delta());
-        if !enemy.sword_hit_timer.finished() {
+    let (player, player_collider, player_transform) = match player_q.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    // melee enemies whose attack reaches the player collider
+    let in_reach: Vec<Entity> = evr_collision
+        .read()
+        .filter_map(|ev| match ev {
+            CollisionEvent::EnemyMeleeHitPlayer { enemy } => Some(*enemy),
+            _ => None,
+        })
+        .collect();
+
+    let pos = player_transform.translation;
+    let reach = player_collider.extent(player_transform) + index.enemy_reach + MOVEMENT_MARGIN;
+    for (entity, _) in index.enemies.query(pos, reach) {
+        let (mut enemy, agent, enemy_transform) = match enemy_q.get_mut(entity) {
+            Ok(enemy) => enemy,
+            Err(_) => continue,
+        };
+        if !matches!(agent.action(), Action::Attack) || !enemy.sword_hit_timer.finished() {
             continue;
         }
-        for (player_transform, mut player_health) in player_q.iter_mut() {
-            let direction = player_transform.translation - enemy_transform.translation();
-            if direction.length() <= ENEMY_THRESHOLD {
-                enemy.animation_state = match enemy.animation_state {
-                    EnemyState::Hunting => EnemyState::ReadyBlade,
-                    EnemyState::SwingBlade => EnemyState::ReadyBlade,
-                    EnemyState::ReadyBlade => {
-                        enemy_attack_fx(&mut commands, &asset_server, mixer);
-                        if player_health.damage(1.0) {
-                            next_state.set(AppState::Death);
-                        }
-                        EnemyState::SwingBlade
+        let direction = pos - enemy_transform.translation;
+        let in_range = match enemy.attack {
+            EnemyAttack::Melee => in_reach.contains(&entity),
+            EnemyAttack::Ranged { .. } => direction.length() <= enemy.stats.attack_range,
+        };
+        if !in_range {
+            enemy.animation_state = EnemyState::Hunting;
+            continue;
+        }
+
+        // ranged enemies aim, shoot and keep going
+        if let EnemyAttack::Ranged { projectile } = &enemy.attack {
+            let state = match enemy.animation_state {
+                EnemyState::ReadyBlade => {
+                    enemy_attack_fx(&mut commands, &enemy.sounds.attack, &mixer);
+                    let volley = projectile.volley.max(1);
+                    for i in 0..volley {
+                        let angle =
+                            (i as f32 - (volley - 1) as f32 / 2.0) * projectile.spread.to_radians();
+                        spawn_enemy_projectile(
+                            &mut commands,
+                            projectile,
+                            enemy.stats.damage,
+                            enemy_transform.translation,
+                            Quat::from_rotation_z(angle) * direction.normalize_or_zero(),
+                        );
                     }
-                };
-                return;
-            } else {
-                enemy.animation_state = EnemyState::Hunting;
-            }
+                    EnemyState::SwingBlade
+                }
+                _ => EnemyState::ReadyBlade,
+            };
+            enemy.animation_state = state;
+            continue;
         }
+        enemy.animation_state = match enemy.animation_state {
+            EnemyState::Hunting => EnemyState::ReadyBlade,
+            EnemyState::SwingBlade => EnemyState::ReadyBlade,
+            EnemyState::ReadyBlade => {
+                enemy_attack_fx(&mut commands, &enemy.sounds.attack, &mixer);
+                evw_damage.send(DamageEvent {
+                    target: player,
+                    amount: enemy.stats.damage,
+                    position: pos,
+                    source: enemy_transform.translation,
+                });
+                EnemyState::SwingBlade
+            }
+        };
     }
 }
 
 fn projectile_hit_enemy(
     mut commands: Commands,
-    mut enemy_q: Query<(Entity, &Transform, &mut Health), With<Enemy>>,
-    projectile_q: Query<(Entity, &Transform), With<Projectile>>,
-    asset_server: Res<AssetServer>,
+    mut evr_collision: EventReader<CollisionEvent>,
+    enemy_q: Query<(&Transform, &Health), With<Enemy>>,
+    mut projectile_q: Query<(&mut Projectile, &Transform)>,
+    mut evw_damage: EventWriter<DamageEvent>,
+) {
+    // damage sent this tick, apply_damage only takes it off the health afterwards
+    let mut pending: HashMap<Entity, f32> = HashMap::new();
+    for ev in evr_collision.read() {
+        let (projectile_entity, enemy_entity) = match ev {
+            CollisionEvent::ProjectileHitEnemy { projectile, enemy } => (*projectile, *enemy),
+            _ => continue,
+        };
+        let (mut projectile, projectile_transform) = match projectile_q.get_mut(projectile_entity) {
+            Ok(projectile) => projectile,
+            Err(_) => continue,
+        };
+        let (enemy_transform, enemy_health) = match enemy_q.get(enemy_entity) {
+            Ok(enemy) => enemy,
+            Err(_) => continue,
+        };
+        // an earlier hit of this tick already kills it, don't waste the pierce
+        let pending_damage = pending.entry(enemy_entity).or_default();
+        if enemy_health.health <= *pending_damage || !projectile.hit(enemy_entity) {
+            continue;
+        }
+        *pending_damage += projectile.damage;
+
+        evw_damage.send(DamageEvent {
+            target: enemy_entity,
+            amount: projectile.damage,
+            position: enemy_transform.translation,
+            source: projectile_transform.translation,
+        });
+        if projectile.spent() {
+            commands.entity(projectile_entity).despawn_recursive();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn enemy_damaged(
+    mut commands: Commands,
+    mut evr_damage_taken: EventReader<DamageTaken>,
+    enemy_q: Query<(&Enemy, &Transform, Has<Boss>)>,
     mut evw_enemy_died: EventWriter<EnemyDied>,
     #[cfg(feature = "storage")] mut score: ResMut<bevy_persistent::Persistent<Score>>,
     #[cfg(not(feature = "storage"))] mut score: ResMut<Score>,
     #[cfg(not(feature = "storage"))] mixer: Res<Mixer>,
     #[cfg(feature = "storage")] mixer: Res<bevy_persistent::Persistent<Mixer>>,
 ) {
-    for (projectile_entity, projectile_transform) in projectile_q.iter() {
-        for (enemy_entity, enemy_transform, mut enemy_health) in enemy_q.iter_mut() {
-            if projectile_transform
-                .translation
-                .distance(enemy_transform.translation)
-                <= 64.0
-            {
-                if enemy_health.damage(1.0) {
-                    evw_enemy_died.send(EnemyDied {
-                        pos: enemy_transform.translation,
-                    });
-                    commands.entity(enemy_entity).despawn_recursive();
-                    score.increase(1);
-                }
-                enemy_hit_fx(&mut commands, &asset_server, mixer);
-                commands.entity(projectile_entity).despawn_recursive();
-                return;
-            }
+    for ev in evr_damage_taken.read() {
+        let (enemy, enemy_transform, boss) = match enemy_q.get(ev.target) {
+            Ok(enemy) => enemy,
+            Err(_) => continue,
+        };
+        if ev.killed {
+            evw_enemy_died.send(EnemyDied {
+                pos: enemy_transform.translation,
+                death: enemy.death.clone(),
+                boss,
+                score: enemy.stats.score,
+            });
+            commands.entity(ev.target).despawn_recursive();
+            score.increase(enemy.stats.score);
         }
+        enemy_hit_fx(&mut commands, &enemy.sounds.hit, &mixer);
     }
 }
 
-fn enemy_hit_fx(
-    commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
-    #[cfg(not(feature = "storage"))] mixer: Res<Mixer>,
-    #[cfg(feature = "storage")] mixer: Res<bevy_persistent::Persistent<Mixer>>,
+/**
+ * Switches to the next phase of the archetype once the health drops below its threshold
+ */
+fn update_phase(
+    mut enemy_q: Query<(&mut Enemy, &mut BehaviorAgent, &Health, &mut Velocity)>,
+    mut rng: ResMut<RunRng>,
 ) {
+    for (mut enemy, mut agent, health, mut velocity) in enemy_q.iter_mut() {
+        let fraction = health.health / health.max_health;
+        let reached = enemy
+            .phases
+            .iter()
+            .take_while(|phase| fraction < phase.below)
+            .count();
+        while enemy.phase < reached {
+            let phase = enemy.phases[enemy.phase].clone();
+            enemy.phase += 1;
+
+            velocity.speed = enemy.stats.speed * enemy.speed_scale * phase.speed_scale;
+            if let Some(attack_interval) = phase.attack_interval {
+                enemy.stats.attack_interval = attack_interval;
+                enemy.sword_hit_timer = Timer::from_seconds(attack_interval, TimerMode::Repeating);
+            }
+            if let Some(attack_range) = phase.attack_range {
+                enemy.stats.attack_range = attack_range;
+            }
+            if let Some(color) = phase.color {
+                enemy.color = color;
+            }
+            if let Some(attack) = phase.attack {
+                enemy.attack = attack;
+                enemy.animation_state = EnemyState::Hunting;
+            }
+            if let Some(behavior) = phase.behavior {
+                agent.set_behavior(behavior, &mut rng.enemy_target);
+            }
+        }
+    }
+}
+
+fn enemy_hit_fx(commands: &mut Commands, source: &Handle<AudioSource>, mixer: &Mixer) {
     commands.spawn(AudioBundle {
-        source: asset_server.load("sounds/69_Enemy_death_01.wav"),
+        source: source.clone(),
         settings: PlaybackSettings {
             mode: PlaybackMode::Despawn,
             volume: mixer.as_volume(),
@@ -213,14 +439,9 @@ fn enemy_hit_fx(
     });
 }
 
-fn enemy_attack_fx(
-    commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
-    #[cfg(not(feature = "storage"))] mixer: Res<Mixer>,
-    #[cfg(feature = "storage")] mixer: Res<bevy_persistent::Persistent<Mixer>>,
-) {
+fn enemy_attack_fx(commands: &mut Commands, source: &Handle<AudioSource>, mixer: &Mixer) {
     commands.spawn(AudioBundle {
-        source: asset_server.load("sounds/56_Attack_03.wav"),
+        source: source.clone(),
         settings: PlaybackSettings {
             mode: PlaybackMode::Despawn,
             volume: mixer.as_volume(),
@@ -229,89 +450,142 @@ fn enemy_attack_fx(
     });
 }
 
-fn switch_target(
-    mut enemy_q: Query<&mut Enemy>,
+fn senses(
+    transform: &Transform,
+    health: &Health,
+    player_transform: &Transform,
+    player_velocity: &Velocity,
+) -> Senses {
+    Senses {
+        position: transform.translation,
+        player_position: player_transform.translation,
+        player_direction: player_velocity.direction,
+        health: health.health / health.max_health,
+    }
+}
+
+fn think(
+    mut enemy_q: Query<(&mut BehaviorAgent, &Transform, &Health), Without<Player>>,
+    player_q: Query<(&Transform, &Velocity), With<Player>>,
     time: Res<Time>,
-    mut rng: ResMut<GlobalEntropy<WyRand>>,
-    player_transform_q: Query<&GlobalTransform, With<Player>>,
+    mut rng: ResMut<RunRng>,
 ) {
-    let player_transform = match player_transform_q.get_single() {
-        Ok(player_transform) => player_transform,
+    let (player_transform, player_velocity) = match player_q.get_single() {
+        Ok(player) => player,
         Err(_) => return,
     };
-    for mut enemy in enemy_q.iter_mut() {
-        enemy.target_switch_timer.tick(time.delta());
-        if enemy.target_switch_timer.just_finished() {
-            let target = match rng.next_u32() % 3 {
-                0 => EnemyTarget::Player,
-                1 => EnemyTarget::PlayerFuture,
-                _ => EnemyTarget::Location(
-                    player_transform
-                        .translation()
-                        .random_around(&mut rng, 128.0, 512.0)
-                        .xy(),
-                ),
-            };
-            enemy.target = target;
+    for (mut agent, transform, health) in enemy_q.iter_mut() {
+        let senses = senses(transform, health, player_transform, player_velocity);
+        agent.think(&senses, time.delta_seconds(), &mut rng.enemy_target);
+    }
+}
+
+/**
+ * Boids style push away from the other enemies within the crowd radius,
+ * stronger the closer they are
+ */
+fn separation(entity: Entity, position: Vec3, radius: f32, grid: &SpatialGrid) -> Vec3 {
+    let mut push = Vec3::ZERO;
+    for (other, other_position) in grid.query(position, radius) {
+        if other == entity {
+            continue;
         }
+        let away = position - other_position;
+        let distance = away.length();
+        if distance >= radius {
+            continue;
+        }
+        let direction = if distance > 0.0 {
+            away / distance
+        } else {
+            // stacked exactly on top of each other, split up by entity order
+            if entity < other {
+                Vec3::X
+            } else {
+                Vec3::NEG_X
+            }
+        };
+        push += direction * (1.0 - distance / radius);
     }
+    push
 }
 
+#[allow(clippy::type_complexity)]
 fn update_position(
-    mut enemy_q: Query<(&Enemy, &Transform, &mut Velocity), Without<Player>>,
-    player_q: Query<(&Transform, &Velocity), With<Player>>,
+    mut enemy_q: Query<(
+        Entity,
+        &Enemy,
+        &BehaviorAgent,
+        &Transform,
+        &Health,
+        &mut Velocity,
+    )>,
+    player_q: Query<(&Transform, &Velocity), (With<Player>, Without<Enemy>)>,
+    index: Res<SpatialIndex>,
+    flow_field: Res<FlowField>,
+    collision_map: Res<CollisionMap>,
 ) {
-    if let Ok((player_transform, player_velocity)) = player_q.get_single() {
-        for (enemy, enemy_transform, mut enemy_vel) in enemy_q.iter_mut() {
-            let target_pos = match enemy.target {
-                EnemyTarget::Player => player_transform.translation,
-                EnemyTarget::PlayerFuture => {
-                    player_transform.translation + player_velocity.direction * 128.0
-                }
-                EnemyTarget::Location(pos) => pos.xyz(),
-            };
-            let direction = target_pos - enemy_transform.translation;
-            if direction.length() >= ENEMY_THRESHOLD {
-                enemy_vel.direction = direction.normalize();
-            } else {
-                enemy_vel.direction = Vec3::ZERO;
+    let (player_transform, player_velocity) = match player_q.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    for (entity, enemy, agent, transform, health, mut velocity) in enemy_q.iter_mut() {
+        let senses = senses(transform, health, player_transform, player_velocity);
+        let push = separation(
+            entity,
+            transform.translation,
+            enemy.crowd.radius,
+            &index.enemies,
+        );
+        let mut steer = agent.steer(&senses);
+        // hunt around obstacles when the player is out of sight
+        if matches!(agent.action(), Action::Chase { .. })
+            && !collision_map.line_of_sight(
+                transform.translation.xy(),
+                player_transform.translation.xy(),
+            )
+        {
+            if let Some(direction) = flow_field.direction(&collision_map, transform.translation) {
+                steer = direction;
             }
         }
+        velocity.direction =
+            (steer * enemy.crowd.seek + push * enemy.crowd.separation).clamp_length_max(1.0);
     }
 }
 
-fn update_animation(mut enemy_q: Query<(&mut Enemy, &Velocity, &mut AnimationIndices)>) {
-    for (mut enemy, velocity, mut animation_indices) in enemy_q.iter_mut() {
+fn update_animation(
+    mut enemy_q: Query<(&mut Enemy, &Velocity, &mut AnimationIndices, &mut Sprite)>,
+) {
+    for (mut enemy, velocity, mut animation_indices, mut sprite) in enemy_q.iter_mut() {
         if velocity.direction.x < 0.0 {
             enemy.face = Face::Left;
         } else if velocity.direction.x > 0.0 {
             enemy.face = Face::Right;
         }
 
-        animation_indices.first = match enemy.animation_state {
-            EnemyState::Hunting => 0,
-            EnemyState::ReadyBlade => 4,
-            EnemyState::SwingBlade => 8,
-        } + match enemy.face {
-            Face::Left => 0,
-            Face::Right => 2,
+        let row = match enemy.animation_state {
+            EnemyState::Hunting => enemy.animation.hunt_row,
+            EnemyState::ReadyBlade => enemy.animation.ready_row,
+            EnemyState::SwingBlade => enemy.animation.attack_row,
         };
+        animation_indices.first = row * enemy.columns
+            + match enemy.face {
+                Face::Left => 0,
+                Face::Right => enemy.animation.frames,
+            };
 
         animation_indices.last = if velocity.direction.length() > 0.0 {
-            animation_indices.first + 1
+            animation_indices.first + enemy.animation.frames.max(1) - 1
         } else {
             animation_indices.first
         };
-    }
-}
 
-fn increase_difficulty(mut difficulty: ResMut<EnemyDifficulty>, time: Res<Time>) {
-    difficulty.enemy_speed = (difficulty.enemy_speed + time.delta_seconds() * 0.2).min(150.0);
-    difficulty.enemy_max_count = (difficulty.enemy_max_count + time.delta_seconds()).min(100.0);
-}
-
-fn reset_difficulty(mut difficulty: ResMut<EnemyDifficulty>) {
-    *difficulty = EnemyDifficulty::default();
+        sprite.color = match (&enemy.animation_state, enemy.animation.telegraph_color) {
+            (EnemyState::ReadyBlade, Some(telegraph_color)) => rgba(telegraph_color),
+            _ => enemy.color,
+        };
+    }
 }
 
 fn despawn_enemy(mut commands: Commands, enemy_q: Query<Entity, With<Enemy>>) {
@@ -320,50 +594,31 @@ fn despawn_enemy(mut commands: Commands, enemy_q: Query<Entity, With<Enemy>>) {
     }
 }
 
-#[derive(Resource, Debug)]
-pub struct EnemyDifficulty {
-    enemy_speed: f32,
-    enemy_max_count: f32,
-}
-
-impl Default for EnemyDifficulty {
-    fn default() -> Self {
-        Self {
-            enemy_speed: 60.0,
-            enemy_max_count: 32.0,
-        }
-    }
-}
-
-impl EnemyDifficulty {
-    fn get_enemy_speed(&self) -> f32 {
-        self.enemy_speed
-    }
-
-    fn get_enemy_max_count(&self) -> usize {
-        self.enemy_max_count as usize
-    }
-}
-
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(EnemyDifficulty::default())
-            .add_systems(ON_ENTER_GAMEPLAY, reset_difficulty)
-            .add_systems(
-                Update,
-                (
-                    spawn_enemy,
-                    switch_target,
-                    update_position,
-                    enemy_attack,
-                    update_animation,
-                    projectile_hit_enemy,
-                    increase_difficulty,
-                )
-                    .run_if(in_state(AppState::InGame)),
-            )
-            .add_systems(ON_EXIT_GAMEPLAY, despawn_enemy);
+        app.add_systems(
+            FixedUpdate,
+            (
+                (think, update_position)
+                    .chain()
+                    .after(run_boss_director)
+                    .after(update_flow_field)
+                    .after(rebuild_spatial_index)
+                    .in_set(GameplaySet::Steering),
+                (tick_attack_timers, enemy_attack, projectile_hit_enemy)
+                    .chain()
+                    .after(detect_collisions)
+                    .before(apply_damage)
+                    .in_set(GameplaySet::Combat),
+                (enemy_damaged, update_phase)
+                    .chain()
+                    .after(apply_damage)
+                    .in_set(GameplaySet::Combat),
+            ),
+        )
+        .add_systems(Update, update_animation.run_if(in_state(AppState::InGame)))
+        .add_systems(ON_EXIT_GAMEPLAY, despawn_enemy);
     }
 }
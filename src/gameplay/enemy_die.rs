@@ -17,12 +17,16 @@
  */
 
 use crate::gameplay::anim::AnimationIndices;
+use crate::gameplay::archetype::DeathAnimation;
 use crate::state::AppState;
 use bevy::prelude::*;
 
 #[derive(Event)]
 pub struct EnemyDied {
     pub pos: Vec3,
+    pub death: DeathAnimation,
+    pub boss: bool,
+    pub score: u32,
 }
 
 #[derive(Component, Debug)]
@@ -31,34 +35,31 @@ pub struct DeadEnemy {
     pub despawn_timer: Timer,
 }
 
-fn spawn_dead_enemy(
-    mut commands: Commands,
-    mut evr_enemy_died: EventReader<EnemyDied>,
-    asset_server: Res<AssetServer>,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
-) {
+fn spawn_dead_enemy(mut commands: Commands, mut evr_enemy_died: EventReader<EnemyDied>) {
     for ev in evr_enemy_died.read() {
-        let texture = asset_server.load("sprites/skeleton_die.png");
-        let layout = TextureAtlasLayout::from_grid(UVec2::new(128, 128), 4, 1, None, None);
-        let texture_atlas_layout = texture_atlas_layouts.add(layout);
-        let animation_indices = AnimationIndices::new(0, 3);
+        let sprite = &ev.death.sprite;
+        let animation_indices = AnimationIndices::new(0, sprite.frames.max(1) - 1);
 
         commands.spawn((
             DeadEnemy {
-                animation_timer: Timer::from_seconds(0.1, TimerMode::Repeating),
+                animation_timer: Timer::from_seconds(ev.death.frame_time, TimerMode::Repeating),
                 despawn_timer: Timer::from_seconds(4.0, TimerMode::Once),
             },
             SpriteBundle {
-                texture,
+                sprite: Sprite {
+                    color: sprite.color,
+                    ..default()
+                },
+                texture: sprite.texture.clone(),
                 transform: Transform {
                     translation: ev.pos,
-                    scale: Vec3::splat(0.75),
+                    scale: Vec3::splat(sprite.scale),
                     ..default()
                 },
                 ..default()
             },
             TextureAtlas {
-                layout: texture_atlas_layout,
+                layout: sprite.layout.clone(),
                 index: animation_indices.first,
             },
             animation_indices,
@@ -0,0 +1,305 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use bevy::prelude::*;
+
+use crate::ext::FRng;
+use crate::gameplay::boss::run_boss_director;
+use crate::gameplay::collision::{Collider, MOVEMENT_MARGIN};
+use crate::gameplay::enemy_die::EnemyDied;
+use crate::gameplay::input::PlayerInput;
+use crate::gameplay::movement::*;
+use crate::gameplay::player::Player;
+use crate::gameplay::spatial::{rebuild_spatial_index, SpatialIndex};
+use crate::gameplay::stats::{PlayerStats, Stat, StatModifier};
+use crate::gameplay::GameplaySet;
+use crate::replay::ReplayInputSet;
+use crate::seed::RunRng;
+use crate::state::{ON_ENTER_GAMEPLAY, ON_EXIT_GAMEPLAY};
+
+const GEM_SIZE: f32 = 12.0;
+/// gems closer to the player than this fly towards them
+const GEM_MAGNET_RADIUS: f32 = 150.0;
+const GEM_SPEED: f32 = 400.0;
+/// experience needed per level, the next level needs this much more
+const LEVEL_EXPERIENCE: u32 = 5;
+const UPGRADE_CHOICES: usize = 3;
+
+/// cooldown multiplier per fire rate upgrade
+const FIRE_RATE_STEP: f32 = 0.85;
+/// speed multiplier per move speed upgrade
+const MOVE_SPEED_STEP: f32 = 1.1;
+
+#[derive(Component, Debug)]
+pub struct ExperienceGem {
+    pub value: u32,
+}
+
+#[derive(Component, Debug)]
+pub struct Experience {
+    pub level: u32,
+    /// collected since the last level up
+    pub experience: u32,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self {
+            level: 1,
+            experience: 0,
+        }
+    }
+}
+
+impl Experience {
+    pub fn needed(&self) -> u32 {
+        LEVEL_EXPERIENCE * self.level
+    }
+
+    /**
+     * Returns the progress to the next level [0.0, 100.0]
+     */
+    pub fn experience_percentage(&self) -> f32 {
+        (self.experience as f32 / self.needed() as f32 * 100.0).min(100.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upgrade {
+    FireRate,
+    ProjectileCount,
+    MoveSpeed,
+    MaxHealth,
+    Pierce,
+}
+
+impl Upgrade {
+    const ALL: [Upgrade; 5] = [
+        Upgrade::FireRate,
+        Upgrade::ProjectileCount,
+        Upgrade::MoveSpeed,
+        Upgrade::MaxHealth,
+        Upgrade::Pierce,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Upgrade::FireRate => "Fire Rate",
+            Upgrade::ProjectileCount => "Multishot",
+            Upgrade::MoveSpeed => "Move Speed",
+            Upgrade::MaxHealth => "Max Health",
+            Upgrade::Pierce => "Pierce",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Upgrade::FireRate => "Spells cool down 15% faster",
+            Upgrade::ProjectileCount => "Spells shoot one more projectile",
+            Upgrade::MoveSpeed => "Move 10% faster",
+            Upgrade::MaxHealth => "One more heart, and heal it",
+            Upgrade::Pierce => "Projectiles pass through one more enemy",
+        }
+    }
+
+    /**
+     * Stays on the player for the rest of the run
+     */
+    pub fn modifier(&self) -> StatModifier {
+        match self {
+            Upgrade::FireRate => StatModifier::multiply(Stat::Cooldown, FIRE_RATE_STEP),
+            Upgrade::ProjectileCount => StatModifier::add(Stat::ProjectileCount, 1.0),
+            Upgrade::MoveSpeed => StatModifier::multiply(Stat::MoveSpeed, MOVE_SPEED_STEP),
+            Upgrade::MaxHealth => StatModifier::add(Stat::MaxHealth, 1.0),
+            Upgrade::Pierce => StatModifier::add(Stat::Pierce, 1.0),
+        }
+    }
+}
+
+/**
+ * The upgrades offered for the current level up,
+ * the simulation waits until one is chosen
+ */
+#[derive(Resource, Debug, Default)]
+pub struct LevelUp {
+    pub choices: Option<[Upgrade; UPGRADE_CHOICES]>,
+}
+
+pub fn no_level_up_pending(level_up: Res<LevelUp>) -> bool {
+    level_up.choices.is_none()
+}
+
+fn roll_upgrades(rng: &mut RunRng) -> [Upgrade; UPGRADE_CHOICES] {
+    let mut pool = Upgrade::ALL;
+    for i in 0..UPGRADE_CHOICES {
+        let remaining = pool.len() - i;
+        let j = i + ((rng.upgrades.next_f32() * remaining as f32) as usize).min(remaining - 1);
+        pool.swap(i, j);
+    }
+    std::array::from_fn(|i| pool[i])
+}
+
+fn drop_gems(mut commands: Commands, mut evr_enemy_died: EventReader<EnemyDied>) {
+    for ev in evr_enemy_died.read() {
+        if ev.score == 0 {
+            continue;
+        }
+
+        let color = if ev.score >= 10 {
+            Color::srgb(0.7, 0.3, 0.9)
+        } else if ev.score >= 2 {
+            Color::srgb(0.3, 0.6, 1.0)
+        } else {
+            Color::srgb(0.3, 0.9, 0.4)
+        };
+
+        commands.spawn((
+            ExperienceGem { value: ev.score },
+            Collider::circle(GEM_SIZE),
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::splat(GEM_SIZE)),
+                    ..default()
+                },
+                transform: Transform {
+                    translation: ev.pos,
+                    rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+                    ..default()
+                },
+                ..default()
+            },
+            MovingObjectBundle {
+                velocity: Velocity::from_vec3(Vec3::ZERO, GEM_SPEED),
+                ..default()
+            },
+        ));
+    }
+}
+
+fn attract_gems(
+    mut gem_q: Query<(&Transform, &mut Velocity), With<ExperienceGem>>,
+    player_q: Query<&Transform, With<Player>>,
+) {
+    let player_pos = match player_q.get_single() {
+        Ok(player_transform) => player_transform.translation,
+        Err(_) => return,
+    };
+
+    for (transform, mut velocity) in gem_q.iter_mut() {
+        let offset = (player_pos - transform.translation).truncate();
+        velocity.direction = if offset.length() <= GEM_MAGNET_RADIUS {
+            offset.normalize_or_zero().extend(0.0)
+        } else {
+            Vec3::ZERO
+        };
+    }
+}
+
+pub(super) fn collect_gems(
+    mut commands: Commands,
+    gem_q: Query<(&ExperienceGem, &Collider, &Transform)>,
+    mut player_q: Query<(&mut Experience, &Collider, &Transform), With<Player>>,
+    index: Res<SpatialIndex>,
+    mut level_up: ResMut<LevelUp>,
+    mut rng: ResMut<RunRng>,
+) {
+    let (mut experience, player_collider, player_transform) = match player_q.get_single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    let pos = player_transform.translation;
+    let reach = player_collider.extent(player_transform) + index.gems.extent() + MOVEMENT_MARGIN;
+    for (entity, _) in index.gems.query(pos, reach) {
+        let (gem, collider, transform) = match gem_q.get(entity) {
+            Ok(gem) => gem,
+            Err(_) => continue,
+        };
+        if collider.overlaps(transform, player_collider, player_transform) {
+            experience.experience += gem.value;
+            commands.entity(entity).despawn();
+        }
+    }
+
+    // one level up at a time, the rest follows once an upgrade is chosen
+    if experience.experience >= experience.needed() {
+        experience.experience -= experience.needed();
+        experience.level += 1;
+        level_up.choices = Some(roll_upgrades(&mut rng));
+    }
+}
+
+fn choose_upgrade(
+    mut input: ResMut<PlayerInput>,
+    mut level_up: ResMut<LevelUp>,
+    mut player_q: Query<&mut PlayerStats, With<Player>>,
+) {
+    let choice = match input.upgrade.take() {
+        Some(choice) => choice,
+        None => return,
+    };
+    let upgrade = match level_up
+        .choices
+        .and_then(|choices| choices.get(choice).copied())
+    {
+        Some(upgrade) => upgrade,
+        None => return,
+    };
+    let mut stats = match player_q.get_single_mut() {
+        Ok(stats) => stats,
+        Err(_) => return,
+    };
+
+    stats.add_modifier(upgrade.modifier());
+    level_up.choices = None;
+}
+
+fn despawn_gems(mut commands: Commands, gem_q: Query<Entity, With<ExperienceGem>>) {
+    for entity in gem_q.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn reset_level_up(mut level_up: ResMut<LevelUp>) {
+    *level_up = LevelUp::default();
+}
+
+pub struct ExperiencePlugin;
+
+impl Plugin for ExperiencePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelUp>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    choose_upgrade
+                        .after(ReplayInputSet)
+                        .in_set(GameplaySet::Input),
+                    // the level up check runs after every reader of the kills of the last tick,
+                    // nothing else happens until an upgrade is chosen
+                    (drop_gems, attract_gems, collect_gems)
+                        .chain()
+                        .after(run_boss_director)
+                        .after(rebuild_spatial_index)
+                        .in_set(GameplaySet::Steering),
+                ),
+            )
+            .add_systems(ON_ENTER_GAMEPLAY, reset_level_up)
+            .add_systems(ON_EXIT_GAMEPLAY, (despawn_gems, reset_level_up));
+    }
+}
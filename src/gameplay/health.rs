@@ -18,6 +18,8 @@
 
 use bevy::prelude::*;
 
+use crate::gameplay::GameplaySet;
+
 #[derive(Component, Debug)]
 pub struct Health {
     pub health: f32,
@@ -48,8 +50,61 @@ impl Health {
     }
 }
 
+/**
+ * Takes no damage until the timer finishes
+ */
+#[derive(Component, Debug)]
+pub struct Invulnerable {
+    pub timer: Timer,
+}
+
+impl Invulnerable {
+    pub fn new(seconds: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+        }
+    }
+
+    /**
+     * Lasts at least `seconds` from now on
+     */
+    pub fn extend(&mut self, seconds: f32) {
+        if self.timer.remaining_secs() < seconds {
+            self.timer = Timer::from_seconds(seconds, TimerMode::Once);
+        }
+    }
+}
+
+/**
+ * Systems later in the tick may still extend the timer before the removal is applied,
+ * so it only removes what is still finished by then
+ */
+pub(super) fn tick_invulnerability(
+    mut commands: Commands,
+    mut invulnerable_q: Query<(Entity, &mut Invulnerable)>,
+    time: Res<Time>,
+) {
+    for (entity, mut invulnerable) in invulnerable_q.iter_mut() {
+        if invulnerable.timer.tick(time.delta()).finished() {
+            commands.add(move |world: &mut World| {
+                let finished = world
+                    .get::<Invulnerable>(entity)
+                    .is_some_and(|invulnerable| invulnerable.timer.finished());
+                if finished {
+                    world.entity_mut(entity).remove::<Invulnerable>();
+                }
+            });
+        }
+    }
+}
+
 pub struct HealthPlugin;
 
 impl Plugin for HealthPlugin {
-    fn build(&self, _app: &mut App) {}
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            tick_invulnerability.in_set(GameplaySet::Steering),
+        );
+    }
 }
@@ -18,8 +18,14 @@
 
 use bevy::prelude::*;
 
+use crate::gameplay::boss::{Boss, BossDefeated, BossSpawned};
+use crate::gameplay::experience::Experience;
 use crate::gameplay::health::Health;
+use crate::gameplay::mana::Mana;
+use crate::gameplay::pickup::{ActivePowerUps, PowerUp};
 use crate::gameplay::player::Player;
+use crate::gameplay::spell::{Spellbook, Spells, SpellsHandle};
+use crate::gameplay::wave::{WaveCleared, WaveDirector, WaveStarted};
 use crate::persistent::Score;
 use crate::state::AppState;
 
@@ -29,9 +35,43 @@ struct Hud;
 #[derive(Component, Debug)]
 pub struct HealthBar;
 
+#[derive(Component, Debug)]
+pub struct ManaBar;
+
+#[derive(Component, Debug)]
+pub struct ExperienceBar;
+
 #[derive(Component, Debug)]
 pub struct ScoreText;
 
+#[derive(Component, Debug)]
+struct SpellBar;
+
+#[derive(Component, Debug)]
+struct SpellCooldown {
+    spell: usize,
+}
+
+#[derive(Component, Debug)]
+struct PowerUpBar;
+
+#[derive(Component, Debug)]
+struct PowerUpTimer {
+    index: usize,
+    power_up: PowerUp,
+}
+
+#[derive(Component, Debug)]
+struct BossBar;
+
+#[derive(Component, Debug)]
+struct BossHealthBar;
+
+#[derive(Component, Debug)]
+struct WaveAnnouncement {
+    timer: Timer,
+}
+
 fn spawn_ui(mut commands: Commands) {
     let container = NodeBundle {
         style: Style {
@@ -80,6 +120,76 @@ fn spawn_ui(mut commands: Commands) {
         ..default()
     };
 
+    let mana_bar_outer = NodeBundle {
+        style: Style {
+            width: Val::Vw(20.0),
+            max_width: Val::Px(200.0),
+            height: Val::Vh(3.0),
+            max_height: Val::Px(12.0),
+            margin: UiRect::top(Val::Px(4.0)),
+            padding: UiRect::all(Val::Px(2.0)),
+            ..default()
+        },
+        background_color: Color::srgb(0.1, 0.1, 0.1).into(),
+        ..default()
+    };
+
+    let mana_bar_inner = NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        background_color: Color::srgb(0.2, 0.35, 0.9).into(),
+        ..default()
+    };
+
+    let experience_bar_outer = NodeBundle {
+        style: Style {
+            width: Val::Vw(20.0),
+            max_width: Val::Px(200.0),
+            height: Val::Vh(2.0),
+            max_height: Val::Px(8.0),
+            margin: UiRect::top(Val::Px(4.0)),
+            padding: UiRect::all(Val::Px(2.0)),
+            ..default()
+        },
+        background_color: Color::srgb(0.1, 0.1, 0.1).into(),
+        ..default()
+    };
+
+    let experience_bar_inner = NodeBundle {
+        style: Style {
+            width: Val::Percent(0.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        background_color: Color::srgb(0.9, 0.8, 0.2).into(),
+        ..default()
+    };
+
+    let spell_bar = NodeBundle {
+        style: Style {
+            display: Display::Flex,
+            flex_direction: FlexDirection::Row,
+            column_gap: Val::Px(4.0),
+            margin: UiRect::vertical(Val::Px(4.0)),
+            ..default()
+        },
+        ..default()
+    };
+
+    let power_up_bar = NodeBundle {
+        style: Style {
+            display: Display::Flex,
+            flex_direction: FlexDirection::Row,
+            column_gap: Val::Px(4.0),
+            margin: UiRect::bottom(Val::Px(4.0)),
+            ..default()
+        },
+        ..default()
+    };
+
     let score_text = TextBundle::from_section(
         "",
         TextStyle {
@@ -93,6 +203,14 @@ fn spawn_ui(mut commands: Commands) {
         parent.spawn(health_bar_outer).with_children(|parent| {
             parent.spawn((HealthBar, health_bar_inner));
         });
+        parent.spawn(mana_bar_outer).with_children(|parent| {
+            parent.spawn((ManaBar, mana_bar_inner));
+        });
+        parent.spawn(experience_bar_outer).with_children(|parent| {
+            parent.spawn((ExperienceBar, experience_bar_inner));
+        });
+        parent.spawn((SpellBar, spell_bar));
+        parent.spawn((PowerUpBar, power_up_bar));
         parent.spawn((ScoreText, score_text));
     });
 }
@@ -111,19 +229,399 @@ fn update_health_bar(
     }
 }
 
+fn update_mana_bar(
+    mut query: Query<&mut Style, With<ManaBar>>,
+    player_mana_q: Query<&Mana, With<Player>>,
+) {
+    let player_mana = match player_mana_q.iter().next() {
+        Some(player_mana) => player_mana,
+        None => return,
+    };
+
+    for mut style in query.iter_mut() {
+        style.width = Val::Percent(player_mana.mana_percentage());
+    }
+}
+
+fn update_experience_bar(
+    mut query: Query<&mut Style, With<ExperienceBar>>,
+    player_experience_q: Query<&Experience, With<Player>>,
+) {
+    let player_experience = match player_experience_q.iter().next() {
+        Some(player_experience) => player_experience,
+        None => return,
+    };
+
+    for mut style in query.iter_mut() {
+        style.width = Val::Percent(player_experience.experience_percentage());
+    }
+}
+
+fn spawn_spell_slot(parent: &mut ChildBuilder, spell: usize, name: &str) {
+    let slot = NodeBundle {
+        style: Style {
+            width: Val::Px(40.0),
+            height: Val::Px(40.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        background_color: Color::srgb(0.1, 0.1, 0.1).into(),
+        ..default()
+    };
+
+    // fills up from the bottom while the spell cools down
+    let cooldown = NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(0.0),
+            ..default()
+        },
+        background_color: Color::srgb(0.45, 0.45, 0.5).into(),
+        ..default()
+    };
+
+    let label = TextBundle::from_section(
+        name.chars().next().map(String::from).unwrap_or_default(),
+        TextStyle {
+            font_size: 24.0,
+            color: Color::WHITE,
+            ..default()
+        },
+    );
+
+    parent.spawn(slot).with_children(|parent| {
+        parent.spawn((SpellCooldown { spell }, cooldown));
+        parent.spawn(label);
+    });
+}
+
+fn update_spell_bar(
+    mut commands: Commands,
+    bar_q: Query<(Entity, Option<&Children>), With<SpellBar>>,
+    mut cooldown_q: Query<(&SpellCooldown, &mut Style, &mut BackgroundColor)>,
+    player_q: Query<(&Spellbook, &Mana), With<Player>>,
+    spells: Res<Assets<Spells>>,
+    spells_handle: Res<SpellsHandle>,
+) {
+    let spells = match spells.get(&spells_handle.0) {
+        Some(spells) => spells,
+        None => return,
+    };
+    let (spellbook, mana) = match player_q.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    // the spells can finish loading after the hud spawned
+    for (entity, children) in bar_q.iter() {
+        if children.is_none() {
+            commands.entity(entity).with_children(|parent| {
+                for (index, spell) in spells.spells.iter().enumerate() {
+                    spawn_spell_slot(parent, index, &spell.name);
+                }
+            });
+        }
+    }
+
+    for (cooldown, mut style, mut background) in cooldown_q.iter_mut() {
+        let spell = match spells.spells.get(cooldown.spell) {
+            Some(spell) => spell,
+            None => continue,
+        };
+        let fraction = spellbook.cooldown_fraction(cooldown.spell);
+        style.height = Val::Percent(fraction * 100.0);
+        *background = if fraction < 1.0 {
+            Color::srgb(0.3, 0.3, 0.35)
+        } else if mana.mana < spell.mana {
+            Color::srgb(0.2, 0.25, 0.5)
+        } else {
+            Color::srgb(0.45, 0.45, 0.5)
+        }
+        .into();
+    }
+}
+
+fn spawn_power_up_slot(parent: &mut ChildBuilder, index: usize, power_up: PowerUp) {
+    let slot = NodeBundle {
+        style: Style {
+            width: Val::Px(28.0),
+            height: Val::Px(28.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        background_color: Color::srgb(0.1, 0.1, 0.1).into(),
+        ..default()
+    };
+
+    // empties towards the bottom while the power-up runs out
+    let timer = NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(0.0),
+            ..default()
+        },
+        background_color: power_up.color().into(),
+        ..default()
+    };
+
+    let label = TextBundle::from_section(
+        power_up
+            .name()
+            .chars()
+            .next()
+            .map(String::from)
+            .unwrap_or_default(),
+        TextStyle {
+            font_size: 18.0,
+            color: Color::BLACK,
+            ..default()
+        },
+    );
+
+    parent.spawn(slot).with_children(|parent| {
+        parent.spawn((PowerUpTimer { index, power_up }, timer));
+        parent.spawn(label);
+    });
+}
+
+fn update_power_up_bar(
+    mut commands: Commands,
+    bar_q: Query<Entity, With<PowerUpBar>>,
+    mut timer_q: Query<(&PowerUpTimer, &mut Style)>,
+    player_q: Query<&ActivePowerUps, With<Player>>,
+) {
+    let power_ups = match player_q.get_single() {
+        Ok(power_ups) => power_ups,
+        Err(_) => return,
+    };
+
+    // respawn the icons whenever a power-up starts or runs out
+    let mut shown: Vec<(usize, PowerUp)> = timer_q
+        .iter()
+        .map(|(timer, _)| (timer.index, timer.power_up))
+        .collect();
+    shown.sort_by_key(|(index, _)| *index);
+    if !shown
+        .iter()
+        .map(|(_, power_up)| *power_up)
+        .eq(power_ups.active.iter().map(|active| active.power_up))
+    {
+        for entity in bar_q.iter() {
+            commands
+                .entity(entity)
+                .despawn_descendants()
+                .with_children(|parent| {
+                    for (index, active) in power_ups.active.iter().enumerate() {
+                        spawn_power_up_slot(parent, index, active.power_up);
+                    }
+                });
+        }
+        return;
+    }
+
+    for (timer, mut style) in timer_q.iter_mut() {
+        if let Some(active) = power_ups.active.get(timer.index) {
+            style.height = Val::Percent(active.timer.fraction_remaining() * 100.0);
+        }
+    }
+}
+
+fn spawn_boss_bar(commands: &mut Commands, title: &str) {
+    let container = NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            bottom: Val::VMin(5.0),
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        ..default()
+    };
+
+    let title_text = TextBundle::from_section(
+        title,
+        TextStyle {
+            font_size: 32.0,
+            color: Color::WHITE,
+            ..default()
+        },
+    );
+
+    let health_bar_outer = NodeBundle {
+        style: Style {
+            width: Val::Vw(50.0),
+            max_width: Val::Px(600.0),
+            height: Val::Vh(3.0),
+            max_height: Val::Px(16.0),
+            padding: UiRect::all(Val::Px(2.0)),
+            ..default()
+        },
+        background_color: Color::srgb(0.1, 0.1, 0.1).into(),
+        ..default()
+    };
+
+    let health_bar_inner = NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        background_color: Color::srgb(0.5, 0.1, 0.6).into(),
+        ..default()
+    };
+
+    commands
+        .spawn((Hud, BossBar, container))
+        .with_children(|parent| {
+            parent.spawn(title_text);
+            parent.spawn(health_bar_outer).with_children(|parent| {
+                parent.spawn((BossHealthBar, health_bar_inner));
+            });
+        });
+}
+
+fn update_boss_bar(
+    mut commands: Commands,
+    boss_q: Query<(&Boss, &Health)>,
+    bar_q: Query<Entity, With<BossBar>>,
+    mut health_bar_q: Query<&mut Style, With<BossHealthBar>>,
+) {
+    let (boss, boss_health) = match boss_q.iter().next() {
+        Some(boss) => boss,
+        None => {
+            for entity in bar_q.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            return;
+        }
+    };
+
+    if bar_q.is_empty() {
+        spawn_boss_bar(&mut commands, &boss.title);
+        return;
+    }
+
+    for mut style in health_bar_q.iter_mut() {
+        style.width = Val::Percent(boss_health.health_percentage());
+    }
+}
+
 fn update_score_text(
     mut query: Query<&mut Text, With<ScoreText>>,
+    player_experience_q: Query<&Experience, With<Player>>,
+    director: Res<WaveDirector>,
     #[cfg(feature = "storage")] score: Res<bevy_persistent::Persistent<Score>>,
     #[cfg(not(feature = "storage"))] score: Res<Score>,
 ) {
+    let level = player_experience_q
+        .iter()
+        .next()
+        .map(|experience| experience.level)
+        .unwrap_or(1);
+
     for mut text in query.iter_mut() {
         text.sections[0].value = format!(
-            "Score: {}\nHigh Score: {}",
-            score.current_score, score.high_score
+            "Score: {}\nHigh Score: {}\nWave: {}\nLevel: {}",
+            score.current_score,
+            score.high_score(),
+            director.number,
+            level
         );
     }
 }
 
+fn announce_wave(
+    mut commands: Commands,
+    mut evr_wave_started: EventReader<WaveStarted>,
+    mut evr_wave_cleared: EventReader<WaveCleared>,
+    mut evr_boss_spawned: EventReader<BossSpawned>,
+    mut evr_boss_defeated: EventReader<BossDefeated>,
+    announcement_q: Query<Entity, With<WaveAnnouncement>>,
+) {
+    let message = evr_wave_started
+        .read()
+        .map(|ev| format!("Wave {}", ev.number))
+        .chain(
+            evr_wave_cleared
+                .read()
+                .map(|ev| format!("Wave {} cleared", ev.number)),
+        )
+        .chain(evr_boss_spawned.read().map(|ev| ev.title.clone()))
+        .chain(
+            evr_boss_defeated
+                .read()
+                .map(|ev| format!("{} defeated", ev.title)),
+        )
+        .last();
+    let message = match message {
+        Some(message) => message,
+        None => return,
+    };
+
+    for entity in announcement_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    commands
+        .spawn((
+            Hud,
+            WaveAnnouncement {
+                timer: Timer::from_seconds(2.5, TimerMode::Once),
+            },
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    top: Val::Vh(20.0),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                message,
+                TextStyle {
+                    font_size: 64.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn fade_wave_announcement(
+    mut commands: Commands,
+    mut announcement_q: Query<(Entity, &mut WaveAnnouncement, &Children)>,
+    mut text_q: Query<&mut Text>,
+    time: Res<Time>,
+) {
+    for (entity, mut announcement, children) in announcement_q.iter_mut() {
+        announcement.timer.tick(time.delta());
+        if announcement.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        for child in children.iter() {
+            if let Ok(mut text) = text_q.get_mut(*child) {
+                let alpha = (announcement.timer.fraction_remaining() * 2.0).min(1.0);
+                for section in text.sections.iter_mut() {
+                    section.style.color.set_alpha(alpha);
+                }
+            }
+        }
+    }
+}
+
 fn despawn_hud(mut commands: Commands, query: Query<Entity, With<Hud>>) {
     for entity in query.iter() {
         commands.entity(entity).despawn_recursive();
@@ -137,7 +635,18 @@ impl Plugin for HudPlugin {
         app.add_systems(OnEnter(AppState::InGame), spawn_ui)
             .add_systems(
                 Update,
-                (update_health_bar, update_score_text).run_if(in_state(AppState::InGame)),
+                (
+                    update_health_bar,
+                    update_mana_bar,
+                    update_experience_bar,
+                    update_spell_bar,
+                    update_power_up_bar,
+                    update_score_text,
+                    update_boss_bar,
+                    announce_wave,
+                    fade_wave_announcement,
+                )
+                    .run_if(in_state(AppState::InGame)),
             )
             .add_systems(OnExit(AppState::InGame), despawn_hud);
     }
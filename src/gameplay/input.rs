@@ -0,0 +1,252 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use bevy::input::mouse::MouseButtonInput;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+
+use crate::gameplay::experience::LevelUp;
+use crate::gameplay::player::Player;
+use crate::gameplay::GameplaySet;
+use crate::replay::ReplayPlayer;
+use crate::state::AppState;
+
+/**
+ * Everything the player asked for in the current tick.
+ * Filled either from the live devices or from a replay.
+ */
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct PlayerInput {
+    pub movement: Vec2,
+    pub sprint: bool,
+    pub dodge: bool,
+    pub fire: Option<Vec2>,
+    /// which spell `fire` casts, index into the spell list
+    pub spell: usize,
+    /// index of the upgrade picked on the level up screen
+    pub upgrade: Option<usize>,
+}
+
+/// mouse buttons casting the spell with the same index
+const SPELL_MOUSE_BUTTONS: [MouseButton; 2] = [MouseButton::Left, MouseButton::Right];
+/// keys casting a spell towards the cursor
+const SPELL_KEYS: [(KeyCode, usize); 3] =
+    [(KeyCode::KeyQ, 2), (KeyCode::KeyE, 3), (KeyCode::KeyR, 4)];
+/// held while aiming with the right stick to cast another spell than the first
+const SPELL_GAMEPAD_BUTTONS: [(GamepadButtonType, usize); 4] = [
+    (GamepadButtonType::RightTrigger2, 1),
+    (GamepadButtonType::LeftTrigger2, 2),
+    (GamepadButtonType::RightTrigger, 3),
+    (GamepadButtonType::LeftTrigger, 4),
+];
+
+fn cursor_world_position(
+    window_q: &Query<&Window>,
+    camera_q: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let window = window_q.get_single().ok()?;
+    let (camera, camera_transform) = camera_q.get_single().ok()?;
+    window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn read_input(
+    mut input: ResMut<PlayerInput>,
+    player_q: Query<&GlobalTransform, With<Player>>,
+    level_up: Res<LevelUp>,
+
+    // keyboard and cursor click
+    keys: Res<ButtonInput<KeyCode>>,
+    mut mousebtn_evr: EventReader<MouseButtonInput>,
+    window_q: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+
+    // gamepad
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+
+    // touch
+    touches: Res<Touches>,
+) {
+    // fire and dodge stay requested until a tick used them
+    input.movement = Vec2::ZERO;
+    input.sprint = false;
+
+    // keyboard x
+    if keys.any_pressed([KeyCode::ArrowLeft, KeyCode::KeyA]) {
+        input.movement.x = -1.0;
+    } else if keys.any_pressed([KeyCode::ArrowRight, KeyCode::KeyD]) {
+        input.movement.x = 1.0;
+    }
+
+    // keyboard y
+    if keys.any_pressed([KeyCode::ArrowUp, KeyCode::KeyW]) {
+        input.movement.y = 1.0;
+    } else if keys.any_pressed([KeyCode::ArrowDown, KeyCode::KeyS]) {
+        input.movement.y = -1.0;
+    }
+
+    // keyboard sprint
+    input.sprint = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    // keyboard dodge
+    if keys.just_pressed(KeyCode::Space) {
+        input.dodge = true;
+    }
+
+    if let Some(gamepad) = gamepads.iter().next() {
+        // left stick x
+        if let Some(left_stick_x) = axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
+        {
+            if left_stick_x.abs() > 0.1 {
+                input.movement.x += left_stick_x;
+            }
+        }
+
+        // left stick y
+        if let Some(left_stick_y) = axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))
+        {
+            if left_stick_y.abs() > 0.1 {
+                input.movement.y += left_stick_y;
+            }
+        }
+
+        // sprint with face button bottom
+        if buttons.pressed(GamepadButton {
+            gamepad,
+            button_type: GamepadButtonType::South,
+        }) {
+            input.sprint = true;
+        }
+
+        // dodge with face button right
+        if buttons.just_pressed(GamepadButton {
+            gamepad,
+            button_type: GamepadButtonType::East,
+        }) {
+            input.dodge = true;
+        }
+    }
+
+    // the click picking an upgrade must not cast a spell once the game goes on
+    if level_up.choices.is_some() {
+        mousebtn_evr.clear();
+        return;
+    }
+
+    let player_position = match player_q.get_single() {
+        Ok(player_transform) => player_transform.translation().xy(),
+        Err(_) => return,
+    };
+
+    // mouse
+    for ev in mousebtn_evr.read() {
+        if !ev.state.is_pressed() {
+            continue;
+        }
+        let spell = match SPELL_MOUSE_BUTTONS
+            .iter()
+            .position(|button| *button == ev.button)
+        {
+            Some(spell) => spell,
+            None => continue,
+        };
+        if let Some(world_position) = cursor_world_position(&window_q, &camera_q) {
+            input.fire = Some((world_position - player_position).normalize());
+            input.spell = spell;
+            return;
+        }
+    }
+
+    // keyboard spells
+    for (key, spell) in SPELL_KEYS {
+        if !keys.just_pressed(key) {
+            continue;
+        }
+        if let Some(world_position) = cursor_world_position(&window_q, &camera_q) {
+            input.fire = Some((world_position - player_position).normalize());
+            input.spell = spell;
+            return;
+        }
+    }
+
+    // gamepad
+    if let Some(gamepad) = gamepads.iter().next() {
+        let x = axes
+            .get(GamepadAxis::new(gamepad, GamepadAxisType::RightStickX))
+            .unwrap_or(0.0);
+        let y = axes
+            .get(GamepadAxis::new(gamepad, GamepadAxisType::RightStickY))
+            .unwrap_or(0.0);
+
+        let direction = Vec2::new(x, y);
+
+        if direction.length() > 0.25 {
+            input.fire = Some(direction.normalize());
+            input.spell = SPELL_GAMEPAD_BUTTONS
+                .iter()
+                .find(|(button_type, _)| {
+                    buttons.pressed(GamepadButton {
+                        gamepad,
+                        button_type: *button_type,
+                    })
+                })
+                .map(|(_, spell)| *spell)
+                .unwrap_or(0);
+            return;
+        }
+    }
+
+    // touch
+    if let Some(touch) = touches.iter().next() {
+        let (camera, camera_transform) = match camera_q.get_single() {
+            Ok(cam) => cam,
+            Err(_) => return,
+        };
+
+        if let Some(world_position) =
+            camera.viewport_to_world_2d(camera_transform, touch.position())
+        {
+            input.fire = Some((world_position - player_position).normalize());
+            input.spell = 0;
+        }
+    }
+}
+
+fn consume_actions(mut input: ResMut<PlayerInput>) {
+    input.fire = None;
+    input.dodge = false;
+}
+
+pub struct PlayerInputPlugin;
+
+impl Plugin for PlayerInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerInput>()
+            .add_systems(
+                PreUpdate,
+                read_input.after(InputSystem).run_if(
+                    in_state(AppState::InGame).and_then(not(resource_exists::<ReplayPlayer>)),
+                ),
+            )
+            .add_systems(FixedUpdate, consume_actions.after(GameplaySet::Combat));
+    }
+}
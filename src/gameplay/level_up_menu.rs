@@ -0,0 +1,181 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use bevy::prelude::*;
+
+use crate::controls::ControlType;
+use crate::gameplay::experience::LevelUp;
+use crate::gameplay::input::PlayerInput;
+use crate::replay::ReplayPlayer;
+use crate::state::AppState;
+use crate::style::{text, text_button, text_title, v_space, ButtonId};
+
+/// keys choosing the upgrade with the same index
+const UPGRADE_KEYS: [KeyCode; 3] = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3];
+const UPGRADE_GAMEPAD_BUTTONS: [(GamepadButtonType, &str); 3] = [
+    (GamepadButtonType::DPadLeft, "ui/dpad_left.png"),
+    (GamepadButtonType::North, "ui/face_north.png"),
+    (GamepadButtonType::DPadRight, "ui/dpad_right.png"),
+];
+
+#[derive(Component, Debug)]
+struct LevelUpMenu;
+
+fn spawn_level_up_menu(commands: &mut Commands, asset_server: &AssetServer, level_up: &LevelUp) {
+    let choices = match level_up.choices {
+        Some(choices) => choices,
+        None => return,
+    };
+
+    let container = NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        background_color: Color::srgba(0.0, 0.0, 0.0, 0.6).into(),
+        ..default()
+    };
+
+    commands
+        .spawn((LevelUpMenu, container))
+        .with_children(|parent| {
+            parent.spawn(text_title("Level Up"));
+            parent.spawn(v_space(5.0));
+
+            for (index, upgrade) in choices.iter().enumerate() {
+                let (button, button_text) = text_button(upgrade.title(), index as u8);
+
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            display: Display::Flex,
+                            align_items: AlignItems::Center,
+                            flex_direction: FlexDirection::Row,
+                            column_gap: Val::Px(16.0),
+                            margin: UiRect::vertical(Val::Px(8.0)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn((ControlType::Keyboard, text(format!("{}", index + 1))));
+                        parent.spawn((
+                            ControlType::Gamepad,
+                            ImageBundle {
+                                image: asset_server.load(UPGRADE_GAMEPAD_BUTTONS[index].1).into(),
+                                style: Style {
+                                    width: Val::VMin(6.4),
+                                    height: Val::VMin(6.4),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                        ));
+                        parent.spawn(button).with_children(|parent| {
+                            parent.spawn(button_text);
+                        });
+                        parent.spawn(text(upgrade.description()));
+                    });
+            }
+        });
+}
+
+/**
+ * Shows the menu while upgrades are offered, also after coming back from the pause menu
+ */
+fn update_level_up_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    level_up: Res<LevelUp>,
+    menu_q: Query<Entity, With<LevelUpMenu>>,
+) {
+    match (level_up.choices.is_some(), menu_q.is_empty()) {
+        (true, true) => spawn_level_up_menu(&mut commands, &asset_server, &level_up),
+        (false, false) => {
+            for entity in menu_q.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        _ => {}
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn choose_upgrade(
+    mut input: ResMut<PlayerInput>,
+    level_up: Res<LevelUp>,
+    button_q: Query<(&Interaction, &ButtonId), (Changed<Interaction>, With<Button>)>,
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+) {
+    if level_up.choices.is_none() {
+        return;
+    }
+
+    for (interaction, button_id) in button_q.iter() {
+        if *interaction == Interaction::Pressed {
+            input.upgrade = Some(button_id.id as usize);
+            return;
+        }
+    }
+
+    if let Some(index) = UPGRADE_KEYS.iter().position(|key| keys.just_pressed(*key)) {
+        input.upgrade = Some(index);
+        return;
+    }
+
+    for gamepad in gamepads.iter() {
+        if let Some(index) = UPGRADE_GAMEPAD_BUTTONS.iter().position(|(button_type, _)| {
+            buttons.just_pressed(GamepadButton {
+                gamepad,
+                button_type: *button_type,
+            })
+        }) {
+            input.upgrade = Some(index);
+            return;
+        }
+    }
+}
+
+fn despawn_level_up_menu(mut commands: Commands, query: Query<Entity, With<LevelUpMenu>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub struct LevelUpMenuPlugin;
+
+impl Plugin for LevelUpMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                update_level_up_menu,
+                choose_upgrade.run_if(not(resource_exists::<ReplayPlayer>)),
+            )
+                .run_if(in_state(AppState::InGame)),
+        )
+        .add_systems(OnExit(AppState::InGame), despawn_level_up_menu);
+    }
+}
@@ -0,0 +1,71 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use bevy::prelude::*;
+
+use crate::gameplay::GameplaySet;
+
+#[derive(Component, Debug)]
+pub struct Mana {
+    pub mana: f32,
+    pub max_mana: f32,
+    /// mana per second
+    pub regeneration: f32,
+}
+
+impl Mana {
+    pub fn new(max_mana: f32, regeneration: f32) -> Self {
+        Self {
+            mana: max_mana,
+            max_mana,
+            regeneration,
+        }
+    }
+
+    /**
+     * Returns false and keeps the mana if there is not enough
+     */
+    pub fn spend(&mut self, cost: f32) -> bool {
+        if self.mana < cost {
+            return false;
+        }
+        self.mana -= cost;
+        true
+    }
+
+    /**
+     * Returns the mana percentage [0.0, 100.0]
+     */
+    pub fn mana_percentage(&self) -> f32 {
+        (self.mana / self.max_mana) * 100.0
+    }
+}
+
+pub(super) fn regenerate_mana(mut mana_q: Query<&mut Mana>, time: Res<Time>) {
+    for mut mana in mana_q.iter_mut() {
+        mana.mana = (mana.mana + mana.regeneration * time.delta_seconds()).min(mana.max_mana);
+    }
+}
+
+pub struct ManaPlugin;
+
+impl Plugin for ManaPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, regenerate_mana.in_set(GameplaySet::Combat));
+    }
+}
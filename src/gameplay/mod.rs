@@ -18,32 +18,111 @@
 
 use bevy::prelude::*;
 
-mod anim;
+use crate::gameplay::experience::no_level_up_pending;
+use crate::ldtk::LevelBounds;
+use crate::state::AppState;
+
+pub mod anim;
+pub mod archetype;
+mod behavior;
+pub mod boss;
 mod cat;
-mod enemy;
+pub mod collision;
+pub mod damage;
+pub mod dodge;
+pub mod enemy;
 mod enemy_die;
-mod health;
+pub mod experience;
+pub mod health;
 mod hud;
+pub mod input;
+mod level_up_menu;
+pub mod mana;
 pub mod movement;
+mod navigation;
 pub mod overlap;
 mod pause_menu;
+pub mod pickup;
 pub mod player;
 mod projectile;
+mod spatial;
+pub mod spell;
+pub mod stats;
+mod wave;
+
+/**
+ * The simulation steps of a fixed tick, in the order they run
+ */
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameplaySet {
+    Input,
+    Steering,
+    Movement,
+    Combat,
+}
+
+/**
+ * A frame can run multiple ticks, but state changes only apply between frames.
+ * Stop simulating once the run is over to keep replays exact.
+ */
+fn no_transition_pending(next_state: Res<NextState<AppState>>) -> bool {
+    matches!(*next_state, NextState::Unchanged)
+}
 
 pub struct GameplayPlugin;
 
 impl Plugin for GameplayPlugin {
     fn build(&self, app: &mut App) {
+        app.configure_sets(
+            FixedUpdate,
+            (
+                GameplaySet::Input,
+                GameplaySet::Steering,
+                GameplaySet::Movement,
+                GameplaySet::Combat,
+            )
+                .chain()
+                .run_if(
+                    in_state(AppState::InGame)
+                        .and_then(no_transition_pending)
+                        .and_then(resource_exists::<LevelBounds>),
+                ),
+        );
+        // configured one by one so a level up stops the rest of the tick it happens in,
+        // the input keeps running to choose the upgrade
+        for set in [
+            GameplaySet::Steering,
+            GameplaySet::Movement,
+            GameplaySet::Combat,
+        ] {
+            app.configure_sets(FixedUpdate, set.run_if(no_level_up_pending));
+        }
+
         app.add_plugins(anim::AnimPlugin)
+            .add_plugins(archetype::ArchetypePlugin)
+            .add_plugins(boss::BossPlugin)
             .add_plugins(cat::CatPlugin)
+            .add_plugins(damage::DamagePlugin)
+            .add_plugins(dodge::DodgePlugin)
+            .add_plugins(collision::CollisionPlugin)
             .add_plugins(enemy::EnemyPlugin)
             .add_plugins(enemy_die::EnemyDiePlugin)
+            .add_plugins(experience::ExperiencePlugin)
             .add_plugins(health::HealthPlugin)
             .add_plugins(hud::HudPlugin)
+            .add_plugins(input::PlayerInputPlugin)
+            .add_plugins(level_up_menu::LevelUpMenuPlugin)
+            .add_plugins(mana::ManaPlugin)
             .add_plugins(movement::MovementPlugin)
+            .add_plugins(navigation::NavigationPlugin)
             .add_plugins(overlap::OverlapPlugin)
             .add_plugins(pause_menu::PauseMenuPlugin)
+            .add_plugins(pickup::PickupPlugin)
             .add_plugins(player::PlayerPlugin)
-            .add_plugins(projectile::ProjectilePlugin);
+            .add_plugins(projectile::ProjectilePlugin)
+            .add_plugins(spatial::SpatialPlugin)
+            .add_plugins(spell::SpellPlugin)
+            .add_plugins(stats::StatsPlugin)
+            .add_plugins(wave::WavePlugin);
     }
 }
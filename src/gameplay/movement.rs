@@ -18,78 +18,197 @@
 
 use bevy::math::f32;
 use bevy::prelude::*;
+use bevy::transform::TransformSystem;
 
 use crate::gameplay::player::Player;
-use crate::ldtk::pos_inside_level;
-use crate::state::AppState;
+use crate::gameplay::GameplaySet;
+use crate::ldtk::{CollisionMap, LevelBounds};
 
-#[derive(Component, Debug)]
+/// how fast impulses fade out, per second
+const IMPULSE_DAMPING: f32 = 10.0;
+
+#[derive(Component, Debug, Default)]
 pub struct Velocity {
     pub direction: Vec3,
     pub speed: f32,
+    /// pixels per second on top of the steered movement, like a knockback
+    pub impulse: Vec3,
 }
 
 impl Velocity {
     pub fn from_vec3(direction: Vec3, speed: f32) -> Self {
-        Self { direction, speed }
+        Self {
+            direction,
+            speed,
+            impulse: Vec3::ZERO,
+        }
     }
 
     pub fn from_vec2(direction: Vec2, speed: f32) -> Self {
         Self {
             direction: Vec3::new(direction.x, direction.y, 0.0),
             speed,
+            impulse: Vec3::ZERO,
+        }
+    }
+
+    fn fade_impulse(&mut self, delta_seconds: f32) {
+        self.impulse *= (-IMPULSE_DAMPING * delta_seconds).exp();
+        if self.impulse.length_squared() < 1.0 {
+            self.impulse = Vec3::ZERO;
+        }
+    }
+}
+
+/**
+ * Smooths the movement between two fixed ticks for rendering.
+ * The `Transform` always holds the simulated position, only the `GlobalTransform` is interpolated.
+ */
+#[derive(Component, Debug, Default)]
+pub struct TransformInterpolation {
+    previous: Option<Vec3>,
+}
+
+/**
+ * A box around the position that slides along solid tiles instead of entering them
+ */
+#[derive(Component, Debug)]
+pub struct TileCollider {
+    pub half_size: Vec2,
+}
+
+impl Default for TileCollider {
+    fn default() -> Self {
+        Self {
+            half_size: Vec2::splat(16.0),
+        }
+    }
+}
+
+impl TileCollider {
+    /**
+     * Moves one axis at a time so blocked movement slides along walls.
+     * Objects that are already stuck inside a solid tile can move freely to get out.
+     */
+    fn resolve(&self, collision_map: &CollisionMap, from: Vec3, to: Vec3) -> Vec3 {
+        if collision_map.overlaps(from.xy(), self.half_size) {
+            return to;
         }
+
+        let mut pos = from;
+        let step_x = Vec3::new(to.x, pos.y, to.z);
+        if !collision_map.overlaps(step_x.xy(), self.half_size) {
+            pos = step_x;
+        }
+        let step_y = Vec3::new(pos.x, to.y, to.z);
+        if !collision_map.overlaps(step_y.xy(), self.half_size) {
+            pos = step_y;
+        }
+        pos
     }
 }
 
-#[derive(Bundle, Debug)]
+#[derive(Bundle, Debug, Default)]
 pub struct MovingObjectBundle {
     pub velocity: Velocity,
+    pub interpolation: TransformInterpolation,
 }
 
 fn update_position(
-    mut movable_object_q: Query<(&Velocity, &mut Transform), Without<Player>>,
+    mut movable_object_q: Query<
+        (&mut Velocity, &mut Transform, Option<&TileCollider>),
+        Without<Player>,
+    >,
+    collision_map: Res<CollisionMap>,
     time: Res<Time>,
 ) {
-    for (movable_object_velocity, mut movable_object_transform) in movable_object_q.iter_mut() {
+    for (mut movable_object_velocity, mut movable_object_transform, collider) in
+        movable_object_q.iter_mut()
+    {
         let new_translation = movable_object_transform.translation
             + movable_object_velocity.direction.normalize_or_zero()
                 * f32::min(
                     movable_object_velocity.speed,
                     movable_object_velocity.direction.length() * movable_object_velocity.speed,
                 )
-                * time.delta_seconds();
+                * time.delta_seconds()
+            + movable_object_velocity.impulse * time.delta_seconds();
+        movable_object_velocity.fade_impulse(time.delta_seconds());
 
-        movable_object_transform.translation = new_translation;
+        movable_object_transform.translation = match collider {
+            Some(collider) => collider.resolve(
+                &collision_map,
+                movable_object_transform.translation,
+                new_translation,
+            ),
+            None => new_translation,
+        };
     }
 }
 
 fn update_player_position(
-    mut movable_object_q: Query<(&Velocity, &mut Transform), With<Player>>,
+    mut movable_object_q: Query<(&mut Velocity, &TileCollider, &mut Transform), With<Player>>,
+    bounds: Res<LevelBounds>,
+    collision_map: Res<CollisionMap>,
     time: Res<Time>,
 ) {
-    for (movable_object_velocity, mut movable_object_transform) in movable_object_q.iter_mut() {
+    for (mut movable_object_velocity, collider, mut movable_object_transform) in
+        movable_object_q.iter_mut()
+    {
         let new_translation = movable_object_transform.translation
             + movable_object_velocity.direction.normalize_or_zero()
                 * f32::min(
                     movable_object_velocity.speed,
                     movable_object_velocity.direction.length() * movable_object_velocity.speed,
                 )
-                * time.delta_seconds();
+                * time.delta_seconds()
+            + movable_object_velocity.impulse * time.delta_seconds();
+        movable_object_velocity.fade_impulse(time.delta_seconds());
+
+        let new_translation = collider.resolve(
+            &collision_map,
+            movable_object_transform.translation,
+            new_translation,
+        );
 
-        if pos_inside_level(&new_translation) {
+        if bounds.contains(&new_translation) {
             movable_object_transform.translation = new_translation;
         }
     }
 }
 
+fn store_previous_translation(mut query: Query<(&Transform, &mut TransformInterpolation)>) {
+    for (transform, mut interpolation) in query.iter_mut() {
+        interpolation.previous = Some(transform.translation);
+    }
+}
+
+fn interpolate_transform(
+    mut query: Query<(&Transform, &TransformInterpolation, &mut GlobalTransform)>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    let overstep = fixed_time.overstep_fraction();
+    for (transform, interpolation, mut global_transform) in query.iter_mut() {
+        if let Some(previous) = interpolation.previous {
+            *global_transform = GlobalTransform::from(
+                transform.with_translation(previous.lerp(transform.translation, overstep)),
+            );
+        }
+    }
+}
+
 pub struct MovementPlugin;
 
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (update_position, update_player_position).run_if(in_state(AppState::InGame)),
-        );
+        app.add_systems(FixedFirst, store_previous_translation)
+            .add_systems(
+                FixedUpdate,
+                (update_position, update_player_position).in_set(GameplaySet::Movement),
+            )
+            .add_systems(
+                PostUpdate,
+                interpolate_transform.after(TransformSystem::TransformPropagate),
+            );
     }
 }
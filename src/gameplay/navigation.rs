@@ -0,0 +1,181 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use bevy::prelude::*;
+
+use crate::gameplay::player::Player;
+use crate::gameplay::GameplaySet;
+use crate::ldtk::CollisionMap;
+use crate::state::ON_EXIT_GAMEPLAY;
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+const NEIGHBORS: [IVec2; 8] = [
+    IVec2::new(1, 0),
+    IVec2::new(-1, 0),
+    IVec2::new(0, 1),
+    IVec2::new(0, -1),
+    IVec2::new(1, 1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, 1),
+    IVec2::new(-1, -1),
+];
+
+/**
+ * Distance of every free cell of the collision map to the player,
+ * shared by all enemies that hunt the player
+ */
+#[derive(Resource, Debug, Default)]
+pub struct FlowField {
+    /// the cell the field leads to
+    target: Option<IVec2>,
+    columns: i32,
+    cost: Vec<u32>,
+}
+
+impl FlowField {
+    fn index(&self, cell: IVec2) -> usize {
+        (cell.y * self.columns + cell.x) as usize
+    }
+
+    fn cost(&self, collision_map: &CollisionMap, cell: IVec2) -> u32 {
+        if !collision_map.contains(cell) {
+            return u32::MAX;
+        }
+        self.cost.get(self.index(cell)).copied().unwrap_or(u32::MAX)
+    }
+
+    /**
+     * Diagonal steps must not cut the corner of a solid cell
+     */
+    fn passable(collision_map: &CollisionMap, cell: IVec2, offset: IVec2) -> bool {
+        let next = cell + offset;
+        collision_map.contains(next)
+            && !collision_map.is_solid(next)
+            && !collision_map.is_solid(IVec2::new(next.x, cell.y))
+            && !collision_map.is_solid(IVec2::new(cell.x, next.y))
+    }
+
+    fn rebuild(&mut self, collision_map: &CollisionMap, target: IVec2) {
+        self.target = Some(target);
+        self.columns = collision_map.columns();
+        self.cost = vec![u32::MAX; (collision_map.columns() * collision_map.rows()) as usize];
+        if !collision_map.contains(target) {
+            return;
+        }
+
+        // dijkstra from the player outwards
+        let mut open = BinaryHeap::new();
+        let target_index = self.index(target);
+        self.cost[target_index] = 0;
+        open.push(Reverse((0, target.y, target.x)));
+        while let Some(Reverse((cost, y, x))) = open.pop() {
+            let cell = IVec2::new(x, y);
+            if cost > self.cost[self.index(cell)] {
+                continue;
+            }
+            for offset in NEIGHBORS {
+                if !Self::passable(collision_map, cell, offset) {
+                    continue;
+                }
+                let next = cell + offset;
+                let step = if offset.x != 0 && offset.y != 0 {
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                };
+                let next_index = self.index(next);
+                if cost + step < self.cost[next_index] {
+                    self.cost[next_index] = cost + step;
+                    open.push(Reverse((cost + step, next.y, next.x)));
+                }
+            }
+        }
+    }
+
+    /**
+     * Whether there is a path from `pos` to the player,
+     * always true while there is no field to tell
+     */
+    pub fn reachable(&self, collision_map: &CollisionMap, pos: Vec3) -> bool {
+        if self.cost.is_empty() {
+            return true;
+        }
+        self.cost(collision_map, collision_map.cell(pos.xy())) != u32::MAX
+    }
+
+    /**
+     * The direction towards the neighbor cell closest to the player,
+     * `None` outside of the field or where the player can't be reached
+     */
+    pub fn direction(&self, collision_map: &CollisionMap, pos: Vec3) -> Option<Vec3> {
+        let cell = collision_map.cell(pos.xy());
+        let mut best = (self.cost(collision_map, cell), cell);
+        if best.0 == u32::MAX {
+            return None;
+        }
+        for offset in NEIGHBORS {
+            if !Self::passable(collision_map, cell, offset) {
+                continue;
+            }
+            let cost = self.cost(collision_map, cell + offset);
+            if cost < best.0 {
+                best = (cost, cell + offset);
+            }
+        }
+        if best.1 == cell {
+            return None;
+        }
+        let towards = collision_map.cell_center(best.1) - pos.xy();
+        Some(towards.extend(0.0).normalize_or_zero())
+    }
+}
+
+pub(super) fn update_flow_field(
+    mut flow_field: ResMut<FlowField>,
+    collision_map: Res<CollisionMap>,
+    player_q: Query<&Transform, With<Player>>,
+) {
+    let player_transform = match player_q.get_single() {
+        Ok(player_transform) => player_transform,
+        Err(_) => return,
+    };
+    let player_cell = collision_map.cell(player_transform.translation.xy());
+    if flow_field.target == Some(player_cell) && !collision_map.is_changed() {
+        return;
+    }
+    flow_field.rebuild(&collision_map, player_cell);
+}
+
+fn reset_flow_field(mut flow_field: ResMut<FlowField>) {
+    *flow_field = FlowField::default();
+}
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlowField>()
+            .add_systems(FixedUpdate, update_flow_field.in_set(GameplaySet::Steering))
+            .add_systems(ON_EXIT_GAMEPLAY, reset_flow_field);
+    }
+}
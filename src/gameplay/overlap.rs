@@ -18,6 +18,7 @@
 
 use bevy::prelude::*;
 
+use crate::gameplay::GameplaySet;
 use crate::state::AppState;
 
 #[derive(Component, Debug)]
@@ -33,6 +34,11 @@ pub struct OverlapPlugin;
 
 impl Plugin for OverlapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, overlap.run_if(in_state(AppState::InGame)));
+        app.add_systems(
+            FixedUpdate,
+            overlap
+                .after(GameplaySet::Combat)
+                .run_if(in_state(AppState::InGame)),
+        );
     }
 }
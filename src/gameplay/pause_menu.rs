@@ -19,6 +19,7 @@ use bevy::prelude::*;
 
 use crate::controls::ControlType;
 use crate::persistent::Score;
+use crate::seed::RunSeed;
 use crate::state::AppState;
 use crate::style::{ButtonId, text, text_button, text_title, v_space};
 
@@ -30,6 +31,7 @@ fn spawn_pause_menu(
     asset_server: Res<AssetServer>,
     #[cfg(feature = "storage")] score: Res<bevy_persistent::Persistent<Score>>,
     #[cfg(not(feature = "storage"))] score: Res<Score>,
+    seed: Res<RunSeed>,
 ) {
     let container = NodeBundle {
         style: Style {
@@ -52,6 +54,7 @@ fn spawn_pause_menu(
         .with_children(|parent| {
             parent.spawn(text_title("Mageanoid"));
             parent.spawn(text(format!("Score: {}", score.current_score)));
+            parent.spawn(text(format!("Seed: {}", *seed)));
             parent.spawn(v_space(20.0));
 
             parent
@@ -0,0 +1,377 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use bevy::audio::PlaybackMode;
+use bevy::prelude::*;
+
+use crate::ext::FRng;
+use crate::gameplay::boss::run_boss_director;
+use crate::gameplay::collision::{Collider, MOVEMENT_MARGIN};
+use crate::gameplay::enemy_die::EnemyDied;
+use crate::gameplay::experience::collect_gems;
+use crate::gameplay::health::{tick_invulnerability, Health, Invulnerable};
+use crate::gameplay::overlap::StaticObject;
+use crate::gameplay::player::{player_movement, Player};
+use crate::gameplay::projectile::{Projectile, ProjectileOwner};
+use crate::gameplay::spatial::{rebuild_spatial_index, SpatialIndex};
+use crate::gameplay::spell::SpellFx;
+use crate::gameplay::stats::{
+    apply_max_health, tick_stat_modifiers, PlayerStats, Stat, StatModifier,
+};
+use crate::gameplay::GameplaySet;
+use crate::persistent::Mixer;
+use crate::seed::RunRng;
+use crate::state::{AppState, ON_EXIT_GAMEPLAY};
+
+/// chance of a normal enemy to drop a power-up, bosses always drop one
+const DROP_CHANCE: f32 = 0.04;
+const PICKUP_SIZE: f32 = 16.0;
+const PICKUP_LIFETIME: f32 = 15.0;
+/// pickups blink for this long before they disappear
+const PICKUP_BLINK_TIME: f32 = 3.0;
+
+const HEAL_AMOUNT: f32 = 2.0;
+const SPEED_BOOTS_MULTIPLIER: f32 = 1.5;
+const RAPID_FIRE_MULTIPLIER: f32 = 0.5;
+const BOOST_DURATION: f32 = 8.0;
+const INVULNERABILITY_DURATION: f32 = 6.0;
+/// about the visible area around the player
+const BOMB_RADIUS: f32 = 900.0;
+const BOMB_DAMAGE: f32 = 10.0;
+/// the bomb hits in the tick it goes off, this only cleans up
+const BOMB_LIFETIME: f32 = 0.05;
+const BOMB_FX_TIME: f32 = 0.4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUp {
+    Heal,
+    SpeedBoots,
+    RapidFire,
+    Invulnerability,
+    Bomb,
+}
+
+impl PowerUp {
+    /// relative drop weights
+    const DROPS: [(PowerUp, u32); 5] = [
+        (PowerUp::Heal, 4),
+        (PowerUp::SpeedBoots, 3),
+        (PowerUp::RapidFire, 3),
+        (PowerUp::Invulnerability, 2),
+        (PowerUp::Bomb, 1),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PowerUp::Heal => "Heal Potion",
+            PowerUp::SpeedBoots => "Speed Boots",
+            PowerUp::RapidFire => "Rapid Fire",
+            PowerUp::Invulnerability => "Invulnerability",
+            PowerUp::Bomb => "Bomb",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            PowerUp::Heal => Color::srgb(0.9, 0.2, 0.25),
+            PowerUp::SpeedBoots => Color::srgb(0.3, 0.8, 0.9),
+            PowerUp::RapidFire => Color::srgb(1.0, 0.6, 0.1),
+            PowerUp::Invulnerability => Color::srgb(1.0, 0.9, 0.3),
+            PowerUp::Bomb => Color::srgb(0.35, 0.35, 0.4),
+        }
+    }
+
+    /**
+     * How long the effect lasts, instant ones have none
+     */
+    pub fn duration(&self) -> Option<f32> {
+        match self {
+            PowerUp::SpeedBoots | PowerUp::RapidFire => Some(BOOST_DURATION),
+            PowerUp::Invulnerability => Some(INVULNERABILITY_DURATION),
+            PowerUp::Heal | PowerUp::Bomb => None,
+        }
+    }
+
+    fn roll(rng: &mut RunRng) -> PowerUp {
+        let total: u32 = Self::DROPS.iter().map(|(_, weight)| weight).sum();
+        let mut roll = rng.pickups.next_f32() * total as f32;
+        for (power_up, weight) in Self::DROPS {
+            if roll < weight as f32 {
+                return power_up;
+            }
+            roll -= weight as f32;
+        }
+        Self::DROPS[Self::DROPS.len() - 1].0
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Pickup {
+    pub power_up: PowerUp,
+    lifetime: Timer,
+}
+
+#[derive(Debug)]
+pub struct ActivePowerUp {
+    pub power_up: PowerUp,
+    pub timer: Timer,
+}
+
+/**
+ * Timed power-ups the player collected, each pickup stacks on its own
+ */
+#[derive(Component, Debug, Default)]
+pub struct ActivePowerUps {
+    pub active: Vec<ActivePowerUp>,
+}
+
+fn spawn_pickup(commands: &mut Commands, power_up: PowerUp, position: Vec3) {
+    commands
+        .spawn((
+            Pickup {
+                power_up,
+                lifetime: Timer::from_seconds(PICKUP_LIFETIME, TimerMode::Once),
+            },
+            Collider::circle(PICKUP_SIZE / 2.0),
+            SpriteBundle {
+                sprite: Sprite {
+                    color: power_up.color(),
+                    custom_size: Some(Vec2::splat(PICKUP_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_translation(position),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                StaticObject,
+                Text2dBundle {
+                    text: Text::from_section(
+                        power_up
+                            .name()
+                            .chars()
+                            .next()
+                            .map(String::from)
+                            .unwrap_or_default(),
+                        TextStyle {
+                            font_size: 14.0,
+                            color: Color::BLACK,
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_xyz(0.0, 0.0, 0.001),
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn drop_pickups(
+    mut commands: Commands,
+    mut evr_enemy_died: EventReader<EnemyDied>,
+    mut rng: ResMut<RunRng>,
+) {
+    for ev in evr_enemy_died.read() {
+        if !ev.boss && rng.pickups.next_f32() >= DROP_CHANCE {
+            continue;
+        }
+        let power_up = PowerUp::roll(&mut rng);
+        spawn_pickup(&mut commands, power_up, ev.pos);
+    }
+}
+
+fn spawn_bomb(commands: &mut Commands, asset_server: &AssetServer, mixer: &Mixer, position: Vec3) {
+    commands.spawn((
+        Projectile::new(ProjectileOwner::Player, BOMB_DAMAGE, BOMB_LIFETIME).with_pierce(u32::MAX),
+        Collider::circle(BOMB_RADIUS),
+        TransformBundle::from_transform(Transform::from_translation(position)),
+    ));
+
+    // flashes the whole screen
+    commands.spawn((
+        SpellFx {
+            timer: Timer::from_seconds(BOMB_FX_TIME, TimerMode::Once),
+        },
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::srgba(1.0, 0.95, 0.8, 0.6),
+                custom_size: Some(Vec2::splat(BOMB_RADIUS * 2.0)),
+                ..default()
+            },
+            transform: Transform::from_translation(position),
+            ..default()
+        },
+    ));
+
+    commands.spawn(AudioBundle {
+        source: asset_server.load("sounds/04_Fire_explosion_04_medium.wav"),
+        settings: PlaybackSettings {
+            mode: PlaybackMode::Despawn,
+            volume: mixer.as_volume(),
+            ..default()
+        },
+    });
+}
+
+#[allow(clippy::type_complexity)]
+fn collect_pickups(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    pickup_q: Query<(&Pickup, &Collider, &Transform)>,
+    mut player_q: Query<
+        (
+            Entity,
+            &mut Health,
+            &mut PlayerStats,
+            &mut ActivePowerUps,
+            Option<&mut Invulnerable>,
+            &Collider,
+            &Transform,
+        ),
+        With<Player>,
+    >,
+    index: Res<SpatialIndex>,
+    #[cfg(not(feature = "storage"))] mixer: Res<Mixer>,
+    #[cfg(feature = "storage")] mixer: Res<bevy_persistent::Persistent<Mixer>>,
+) {
+    let (
+        player,
+        mut health,
+        mut stats,
+        mut power_ups,
+        mut invulnerable,
+        player_collider,
+        player_transform,
+    ) = match player_q.get_single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    let pos = player_transform.translation;
+    let reach = player_collider.extent(player_transform) + index.pickups.extent() + MOVEMENT_MARGIN;
+    for (entity, _) in index.pickups.query(pos, reach) {
+        let (pickup, collider, transform) = match pickup_q.get(entity) {
+            Ok(pickup) => pickup,
+            Err(_) => continue,
+        };
+        if !collider.overlaps(transform, player_collider, player_transform) {
+            continue;
+        }
+        commands.entity(entity).despawn_recursive();
+
+        let power_up = pickup.power_up;
+        match power_up {
+            PowerUp::Heal => {
+                health.health = (health.health + HEAL_AMOUNT).min(health.max_health);
+            }
+            PowerUp::SpeedBoots => stats.add_modifier(
+                StatModifier::multiply(Stat::MoveSpeed, SPEED_BOOTS_MULTIPLIER)
+                    .with_duration(BOOST_DURATION),
+            ),
+            PowerUp::RapidFire => stats.add_modifier(
+                StatModifier::multiply(Stat::Cooldown, RAPID_FIRE_MULTIPLIER)
+                    .with_duration(BOOST_DURATION),
+            ),
+            PowerUp::Invulnerability => match invulnerable.as_mut() {
+                Some(invulnerable) => invulnerable.extend(INVULNERABILITY_DURATION),
+                None => {
+                    commands
+                        .entity(player)
+                        .insert(Invulnerable::new(INVULNERABILITY_DURATION));
+                }
+            },
+            PowerUp::Bomb => spawn_bomb(
+                &mut commands,
+                &asset_server,
+                &mixer,
+                player_transform.translation,
+            ),
+        }
+
+        if let Some(duration) = power_up.duration() {
+            power_ups.active.push(ActivePowerUp {
+                power_up,
+                timer: Timer::from_seconds(duration, TimerMode::Once),
+            });
+        }
+    }
+}
+
+fn tick_power_ups(mut power_ups_q: Query<&mut ActivePowerUps>, time: Res<Time>) {
+    for mut power_ups in power_ups_q.iter_mut() {
+        power_ups
+            .active
+            .retain_mut(|power_up| !power_up.timer.tick(time.delta()).finished());
+    }
+}
+
+fn expire_pickups(
+    mut commands: Commands,
+    mut pickup_q: Query<(Entity, &mut Pickup)>,
+    time: Res<Time>,
+) {
+    for (entity, mut pickup) in pickup_q.iter_mut() {
+        if pickup.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn blink_pickups(mut pickup_q: Query<(&Pickup, &mut Visibility)>) {
+    for (pickup, mut visibility) in pickup_q.iter_mut() {
+        let remaining = pickup.lifetime.remaining_secs();
+        *visibility = if remaining < PICKUP_BLINK_TIME && (remaining * 4.0).fract() < 0.5 {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+}
+
+fn despawn_pickups(mut commands: Commands, pickup_q: Query<Entity, With<Pickup>>) {
+    for entity in pickup_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                tick_power_ups,
+                expire_pickups,
+                drop_pickups,
+                collect_pickups,
+            )
+                .chain()
+                .after(run_boss_director)
+                .after(rebuild_spatial_index)
+                .after(tick_stat_modifiers)
+                .after(tick_invulnerability)
+                .before(apply_max_health)
+                .before(player_movement)
+                .before(collect_gems)
+                .in_set(GameplaySet::Steering),
+        )
+        .add_systems(Update, blink_pickups.run_if(in_state(AppState::InGame)))
+        .add_systems(ON_EXIT_GAMEPLAY, despawn_pickups);
+    }
+}
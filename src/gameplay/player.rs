@@ -17,30 +17,36 @@
  */
 
 use bevy::audio::PlaybackMode;
-use bevy::input::mouse::MouseButtonInput;
 use bevy::prelude::*;
 
+use crate::ext::IntoVec3;
 use crate::gameplay::anim::*;
+use crate::gameplay::collision::Collider;
+use crate::gameplay::damage::HitFlash;
+use crate::gameplay::dodge::Dodge;
+use crate::gameplay::experience::{no_level_up_pending, Experience};
 use crate::gameplay::health::Health;
+use crate::gameplay::input::PlayerInput;
+use crate::gameplay::mana::Mana;
 use crate::gameplay::movement::*;
-use crate::gameplay::projectile::*;
+use crate::gameplay::pickup::ActivePowerUps;
+use crate::gameplay::spell::Spellbook;
+use crate::gameplay::stats::{tick_stat_modifiers, PlayerStats, Stat};
+use crate::gameplay::GameplaySet;
+use crate::ldtk::SpawnPoints;
 use crate::persistent::Mixer;
 use crate::state::{AppState, ON_ENTER_GAMEPLAY, ON_EXIT_GAMEPLAY};
 
-const PLAYER_MOVE_SPEED: f32 = 175.0;
-
 pub struct PlayerPlugin;
 
 #[derive(Component, Debug)]
 pub struct Player {
-    pub projectile_spawn_timer: Timer,
     pub walk_sound_timer: Timer,
 }
 
 impl Default for Player {
     fn default() -> Self {
         Self {
-            projectile_spawn_timer: Timer::from_seconds(0.2, TimerMode::Once),
             walk_sound_timer: Timer::from_seconds(0.4, TimerMode::Repeating),
         }
     }
@@ -50,19 +56,28 @@ fn spawn_player(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    spawn_points: Res<SpawnPoints>,
 ) {
     let texture = asset_server.load("sprites/mage_walk.png");
     let layout = TextureAtlasLayout::from_grid(UVec2::new(64, 64), 4, 1, None, None);
     let texture_atlas_layout = texture_atlas_layouts.add(layout);
 
     let animation_indices = AnimationIndices::new(0, 0);
+    let stats = PlayerStats::default();
 
     commands.spawn((
         Player::default(),
-        Health::new(5.0),
+        (Health::new(stats.get(Stat::MaxHealth)), HitFlash::default()),
+        Collider::aabb(16.0, 30.0),
+        Mana::new(100.0, 12.0),
+        Spellbook::default(),
+        Dodge::default(),
+        Experience::default(),
+        ActivePowerUps::default(),
         SpriteBundle {
             texture,
-            transform: Transform::from_scale(Vec3::splat(1.0)),
+            transform: Transform::from_scale(Vec3::splat(1.0))
+                .with_translation(spawn_points.player.xyz()),
             ..default()
         },
         TextureAtlas {
@@ -72,130 +87,14 @@ fn spawn_player(
         animation_indices,
         AnimationTimer(Timer::from_seconds(0.25, TimerMode::Repeating)),
         MovingObjectBundle {
-            velocity: Velocity::from_vec3(Vec3::new(0.0, 0.0, 0.0), PLAYER_MOVE_SPEED),
+            velocity: Velocity::from_vec3(Vec3::new(0.0, 0.0, 0.0), stats.get(Stat::MoveSpeed)),
+            ..default()
         },
+        TileCollider::default(),
+        stats,
     ));
 }
 
-#[allow(clippy::too_many_arguments)]
-fn player_projectile(
-    mut player_q: Query<(&mut Player, &GlobalTransform)>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
-    time: Res<Time>,
-
-    // cursor click
-    mut mousebtn_evr: EventReader<MouseButtonInput>,
-    window_q: Query<&Window>,
-    camera_q: Query<(&Camera, &GlobalTransform)>,
-
-    // gamepad
-    gamepads: Res<Gamepads>,
-    axes: Res<Axis<GamepadAxis>>,
-
-    // touch
-    touches: Res<Touches>,
-
-    #[cfg(not(feature = "storage"))] mixer: Res<Mixer>,
-    #[cfg(feature = "storage")] mixer: Res<bevy_persistent::Persistent<Mixer>>,
-) {
-    let (mut player, player_transform) = match player_q.get_single_mut() {
-        Ok(player) => player,
-        Err(_) => return,
-    };
-
-    player.projectile_spawn_timer.tick(time.delta());
-    if !player.projectile_spawn_timer.finished() {
-        return;
-    }
-
-    // mouse
-    for ev in mousebtn_evr.read() {
-        if ev.state.is_pressed() && ev.button == MouseButton::Left {
-            let window = match window_q.get_single() {
-                Ok(window) => window,
-                Err(_) => return,
-            };
-
-            let (camera, camera_transform) = match camera_q.get_single() {
-                Ok(cam) => cam,
-                Err(_) => return,
-            };
-
-            if let Some(world_position) = window
-                .cursor_position()
-                .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
-            {
-                let direction = (world_position - player_transform.translation().xy()).normalize();
-
-                ProjectileBundle::spawn(
-                    &mut commands,
-                    &asset_server,
-                    &mut texture_atlas_layouts,
-                    player_transform.translation(),
-                    direction,
-                    mixer,
-                );
-
-                player.projectile_spawn_timer.reset();
-                return;
-            }
-        }
-    }
-
-    // gamepad
-    if let Some(gamepad) = gamepads.iter().next() {
-        let x = axes
-            .get(GamepadAxis::new(gamepad, GamepadAxisType::RightStickX))
-            .unwrap_or(0.0);
-        let y = axes
-            .get(GamepadAxis::new(gamepad, GamepadAxisType::RightStickY))
-            .unwrap_or(0.0);
-
-        let direction = Vec2::new(x, y);
-
-        if direction.length() > 0.25 {
-            ProjectileBundle::spawn(
-                &mut commands,
-                &asset_server,
-                &mut texture_atlas_layouts,
-                player_transform.translation(),
-                direction.normalize(),
-                mixer,
-            );
-
-            player.projectile_spawn_timer.reset();
-            return;
-        }
-    }
-
-    // touch
-    if let Some(touch) = touches.iter().next() {
-        let (camera, camera_transform) = match camera_q.get_single() {
-            Ok(cam) => cam,
-            Err(_) => return,
-        };
-
-        if let Some(world_position) =
-            camera.viewport_to_world_2d(camera_transform, touch.position())
-        {
-            let direction = (world_position - player_transform.translation().xy()).normalize();
-
-            ProjectileBundle::spawn(
-                &mut commands,
-                &asset_server,
-                &mut texture_atlas_layouts,
-                player_transform.translation(),
-                direction,
-                mixer,
-            );
-
-            player.projectile_spawn_timer.reset();
-        }
-    }
-}
-
 fn player_step_sound_fx(
     mut player_q: Query<(&mut Player, &Velocity)>,
     mut commands: Commands,
@@ -225,66 +124,17 @@ fn player_step_sound_fx(
     }
 }
 
-fn player_movement(
-    mut player_q: Query<(&mut Velocity, &mut AnimationIndices), With<Player>>,
-    keys: Res<ButtonInput<KeyCode>>,
-    gamepads: Res<Gamepads>,
-    axes: Res<Axis<GamepadAxis>>,
-    buttons: Res<ButtonInput<GamepadButton>>,
+pub(super) fn player_movement(
+    mut player_q: Query<(&mut Velocity, &mut AnimationIndices, &PlayerStats), With<Player>>,
+    input: Res<PlayerInput>,
 ) {
-    for (mut player_velocity, mut player_indices) in player_q.iter_mut() {
-        // keyboard x
-        if keys.any_pressed([KeyCode::ArrowLeft, KeyCode::KeyA]) {
-            player_velocity.direction.x = -1.0;
-        } else if keys.any_pressed([KeyCode::ArrowRight, KeyCode::KeyD]) {
-            player_velocity.direction.x = 1.0;
-        } else {
-            player_velocity.direction.x = 0.0;
-        }
-
-        // keyboard y
-        if keys.any_pressed([KeyCode::ArrowUp, KeyCode::KeyW]) {
-            player_velocity.direction.y = 1.0;
-        } else if keys.any_pressed([KeyCode::ArrowDown, KeyCode::KeyS]) {
-            player_velocity.direction.y = -1.0;
-        } else {
-            player_velocity.direction.y = 0.0;
-        }
+    for (mut player_velocity, mut player_indices, stats) in player_q.iter_mut() {
+        player_velocity.direction = input.movement.xyz();
 
-        // keyboard sprint
-        if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
-            player_velocity.speed = PLAYER_MOVE_SPEED * 2.0;
+        if input.sprint {
+            player_velocity.speed = stats.get(Stat::MoveSpeed) * stats.get(Stat::SprintMultiplier);
         } else {
-            player_velocity.speed = PLAYER_MOVE_SPEED;
-        }
-
-        if let Some(gamepad) = gamepads.iter().next() {
-            // left stick x
-            if let Some(left_stick_x) =
-                axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
-            {
-                if left_stick_x.abs() > 0.1 {
-                    player_velocity.direction.x += left_stick_x;
-                }
-            }
-
-            // left stick y
-            if let Some(left_stick_y) =
-                axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))
-            {
-                if left_stick_y.abs() > 0.1 {
-                    player_velocity.direction.y += left_stick_y;
-                }
-            }
-
-            // sprint with face button bottom
-            let sprint_button = GamepadButton {
-                gamepad,
-                button_type: GamepadButtonType::South,
-            };
-            if buttons.pressed(sprint_button) {
-                player_velocity.speed = PLAYER_MOVE_SPEED * 2.0;
-            }
+            player_velocity.speed = stats.get(Stat::MoveSpeed);
         }
 
         // animation face direction
@@ -310,9 +160,14 @@ impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(ON_ENTER_GAMEPLAY, spawn_player);
         app.add_systems(
+            FixedUpdate,
+            player_movement
+                .after(tick_stat_modifiers)
+                .in_set(GameplaySet::Steering),
+        )
+        .add_systems(
             Update,
-            (player_movement, player_step_sound_fx, player_projectile)
-                .run_if(in_state(AppState::InGame)),
+            player_step_sound_fx.run_if(in_state(AppState::InGame).and_then(no_level_up_pending)),
         )
         .add_systems(ON_EXIT_GAMEPLAY, despawn_player);
     }
@@ -16,96 +16,121 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use bevy::audio::PlaybackMode;
 use bevy::prelude::*;
 
-use crate::ext::IntoVec3;
 use crate::gameplay::anim::*;
+use crate::gameplay::collision::{detect_collisions, Collider, CollisionEvent};
+use crate::gameplay::damage::{apply_damage, DamageEvent};
 use crate::gameplay::movement::*;
-use crate::persistent::Mixer;
-use crate::state::{AppState, ON_EXIT_GAMEPLAY};
+use crate::gameplay::player::Player;
+use crate::gameplay::GameplaySet;
+use crate::ldtk::{CollisionMap, LevelBounds};
+use crate::state::ON_EXIT_GAMEPLAY;
+
+/**
+ * Who fired a projectile, projectiles only hit the other side
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectileOwner {
+    Player,
+    Enemy,
+}
 
 #[derive(Component, Debug)]
-pub struct Projectile;
+pub struct Projectile {
+    pub lifetime: Timer,
+    pub owner: ProjectileOwner,
+    pub damage: f32,
+    /// how many distinct enemies it hits before it is gone
+    pub pierce: u32,
+    hits: Vec<Entity>,
+}
+
+impl Projectile {
+    pub fn new(owner: ProjectileOwner, damage: f32, lifetime: f32) -> Self {
+        Self {
+            lifetime: Timer::from_seconds(lifetime, TimerMode::Once),
+            owner,
+            damage,
+            pierce: 1,
+            hits: Vec::new(),
+        }
+    }
+
+    pub fn with_pierce(mut self, pierce: u32) -> Self {
+        self.pierce = pierce.max(1);
+        self
+    }
+
+    pub fn spent(&self) -> bool {
+        self.hits.len() >= self.pierce as usize
+    }
+
+    /**
+     * Remembers the target, false if it was hit before or the projectile is spent
+     */
+    pub fn hit(&mut self, target: Entity) -> bool {
+        if self.spent() || self.hits.contains(&target) {
+            return false;
+        }
+        self.hits.push(target);
+        true
+    }
+}
 
 #[derive(Bundle, Debug)]
 pub struct ProjectileBundle {
     pub projectile: Projectile,
+    pub collider: Collider,
     pub animated_sprite: AnimatedSpriteBundle,
     pub moving_object: MovingObjectBundle,
 }
 
-impl ProjectileBundle {
-    pub fn spawn(
-        commands: &mut Commands,
-        asset_server: &Res<AssetServer>,
-        texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
-        position: Vec3,
-        direction: Vec2,
-        #[cfg(not(feature = "storage"))] mixer: Res<Mixer>,
-        #[cfg(feature = "storage")] mixer: Res<bevy_persistent::Persistent<Mixer>>,
-    ) {
-        let texture = asset_server.load("sprites/projectile.png");
-        let layout = TextureAtlasLayout::from_grid(UVec2::new(64, 64), 4, 1, None, None);
-        let texture_atlas_layout = texture_atlas_layouts.add(layout);
-
-        let animation_indices = AnimationIndices::new(0, 3);
-
-        let projectile = ProjectileBundle {
-            projectile: Projectile,
-            animated_sprite: AnimatedSpriteBundle {
-                sprite: SpriteBundle {
-                    texture,
-                    transform: Transform {
-                        translation: position + (direction * 32.0).xyz(),
-                        rotation: Quat::IDENTITY,
-                        scale: Vec3::ONE,
-                    },
-                    ..default()
-                },
-                atlas: TextureAtlas {
-                    layout: texture_atlas_layout,
-                    index: animation_indices.first,
-                },
-                indices: animation_indices,
-                timer: AnimationTimer(Timer::from_seconds(0.125, TimerMode::Repeating)),
-            },
-            moving_object: MovingObjectBundle {
-                velocity: Velocity::from_vec2(direction, 420.0),
-            },
-        };
-        let attack_sound = AudioBundle {
-            source: asset_server.load("sounds/56_Attack_03.wav"),
-            settings: PlaybackSettings {
-                mode: PlaybackMode::Despawn,
-                volume: mixer.as_volume(),
-                ..default()
-            },
-        };
-        let thunder_sound = AudioBundle {
-            source: asset_server.load("sounds/18_Thunder_02.wav"),
-            settings: PlaybackSettings {
-                mode: PlaybackMode::Despawn,
-                volume: mixer.as_volume_with_multiplier(0.5),
-                ..default()
-            },
-        };
-
-        commands.spawn(projectile).with_children(|parent| {
-            parent.spawn(attack_sound);
-            parent.spawn(thunder_sound);
-        });
+pub(super) fn projectile_out_of_bounds(
+    mut projectile_q: Query<(Entity, &mut Projectile, &Transform)>,
+    mut commands: Commands,
+    bounds: Res<LevelBounds>,
+    collision_map: Res<CollisionMap>,
+    time: Res<Time>,
+) {
+    for (projectile_entity, mut projectile, transform) in projectile_q.iter_mut() {
+        projectile.lifetime.tick(time.delta());
+        if projectile.lifetime.finished()
+            || !bounds.contains(&transform.translation)
+            || collision_map.is_solid_at(transform.translation.xy())
+        {
+            commands.entity(projectile_entity).despawn();
+        }
     }
 }
 
-fn projectile_out_of_bounds(
-    projectile_q: Query<(Entity, &ViewVisibility), With<Projectile>>,
+fn projectile_hit_player(
     mut commands: Commands,
+    mut evr_collision: EventReader<CollisionEvent>,
+    projectile_q: Query<(&Projectile, &Transform)>,
+    player_q: Query<(Entity, &Transform), With<Player>>,
+    mut evw_damage: EventWriter<DamageEvent>,
 ) {
-    for (projectile_entity, view_visibility) in projectile_q.iter() {
-        if !view_visibility.get() {
-            commands.entity(projectile_entity).despawn();
-        }
+    let (player, player_transform) = match player_q.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    for ev in evr_collision.read() {
+        let projectile_entity = match ev {
+            CollisionEvent::ProjectileHitPlayer { projectile } => *projectile,
+            _ => continue,
+        };
+        let (projectile, projectile_transform) = match projectile_q.get(projectile_entity) {
+            Ok(projectile) => projectile,
+            Err(_) => continue,
+        };
+        evw_damage.send(DamageEvent {
+            target: player,
+            amount: projectile.damage,
+            position: player_transform.translation,
+            source: projectile_transform.translation,
+        });
+        commands.entity(projectile_entity).despawn_recursive();
     }
 }
 
@@ -120,8 +145,14 @@ pub struct ProjectilePlugin;
 impl Plugin for ProjectilePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
-            Update,
-            projectile_out_of_bounds.run_if(in_state(AppState::InGame)),
+            FixedUpdate,
+            (
+                projectile_out_of_bounds,
+                projectile_hit_player
+                    .after(detect_collisions)
+                    .before(apply_damage),
+            )
+                .in_set(GameplaySet::Combat),
         )
         .add_systems(ON_EXIT_GAMEPLAY, despawn_projectile);
     }
@@ -0,0 +1,143 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::gameplay::boss::run_boss_director;
+use crate::gameplay::collision::Collider;
+use crate::gameplay::enemy::Enemy;
+use crate::gameplay::experience::ExperienceGem;
+use crate::gameplay::pickup::Pickup;
+use crate::gameplay::GameplaySet;
+use crate::state::ON_EXIT_GAMEPLAY;
+
+const CELL_SIZE: f32 = 128.0;
+
+/**
+ * Buckets positions into square cells so neighbors can be found without checking everything
+ */
+#[derive(Debug, Default)]
+pub struct SpatialGrid {
+    cells: HashMap<IVec2, Vec<(Entity, Vec3)>>,
+    /// largest collider extent inserted since the last clear
+    extent: f32,
+}
+
+impl SpatialGrid {
+    fn cell(pos: Vec3) -> IVec2 {
+        (pos.xy() / CELL_SIZE).floor().as_ivec2()
+    }
+
+    /**
+     * Empties the cells but keeps their memory for the next tick
+     */
+    pub fn clear(&mut self) {
+        for entries in self.cells.values_mut() {
+            entries.clear();
+        }
+        self.extent = 0.0;
+    }
+
+    pub fn insert(&mut self, entity: Entity, pos: Vec3, extent: f32) {
+        self.extent = self.extent.max(extent);
+        self.cells
+            .entry(Self::cell(pos))
+            .or_default()
+            .push((entity, pos));
+    }
+
+    /**
+     * Add this to a query radius to find everything that could touch it
+     */
+    pub fn extent(&self) -> f32 {
+        self.extent
+    }
+
+    /**
+     * Everything within `radius` of `pos`, in a stable order
+     */
+    pub fn query(&self, pos: Vec3, radius: f32) -> impl Iterator<Item = (Entity, Vec3)> + '_ {
+        let min = Self::cell(pos - Vec3::splat(radius));
+        let max = Self::cell(pos + Vec3::splat(radius));
+        let radius_squared = radius * radius;
+        (min.y..=max.y)
+            .flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flat_map(|entries| entries.iter().copied())
+            .filter(move |(_, other)| other.distance_squared(pos) <= radius_squared)
+    }
+}
+
+/**
+ * Broadphase for everything that has to find nearby things, rebuilt every tick
+ */
+#[derive(Resource, Debug, Default)]
+pub struct SpatialIndex {
+    pub enemies: SpatialGrid,
+    /// longest attack range of the indexed enemies
+    pub enemy_reach: f32,
+    pub gems: SpatialGrid,
+    pub pickups: SpatialGrid,
+}
+
+pub(super) fn rebuild_spatial_index(
+    mut index: ResMut<SpatialIndex>,
+    enemy_q: Query<(Entity, &Enemy, &Transform, Option<&Collider>)>,
+    gem_q: Query<(Entity, &Transform, &Collider), With<ExperienceGem>>,
+    pickup_q: Query<(Entity, &Transform, &Collider), With<Pickup>>,
+) {
+    index.enemies.clear();
+    index.enemy_reach = 0.0;
+    for (entity, enemy, transform, collider) in enemy_q.iter() {
+        let extent = collider.map_or(0.0, |collider| collider.extent(transform));
+        index.enemies.insert(entity, transform.translation, extent);
+        index.enemy_reach = index.enemy_reach.max(enemy.attack_range());
+    }
+    index.gems.clear();
+    for (entity, transform, collider) in gem_q.iter() {
+        index
+            .gems
+            .insert(entity, transform.translation, collider.extent(transform));
+    }
+    index.pickups.clear();
+    for (entity, transform, collider) in pickup_q.iter() {
+        index
+            .pickups
+            .insert(entity, transform.translation, collider.extent(transform));
+    }
+}
+
+fn reset_spatial_index(mut index: ResMut<SpatialIndex>) {
+    *index = SpatialIndex::default();
+}
+
+pub struct SpatialPlugin;
+
+impl Plugin for SpatialPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpatialIndex>()
+            .add_systems(
+                FixedUpdate,
+                rebuild_spatial_index
+                    .after(run_boss_director)
+                    .in_set(GameplaySet::Steering),
+            )
+            .add_systems(ON_EXIT_GAMEPLAY, reset_spatial_index);
+    }
+}
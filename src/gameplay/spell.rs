@@ -0,0 +1,711 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::io;
+use std::time::Duration;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::audio::PlaybackMode;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::gameplay::anim::*;
+use crate::gameplay::archetype::{rgba, SpriteSheet, SpriteSheetDef};
+use crate::gameplay::collision::{detect_collisions, Collider, MOVEMENT_MARGIN};
+use crate::gameplay::enemy::Enemy;
+use crate::gameplay::input::PlayerInput;
+use crate::gameplay::mana::{regenerate_mana, Mana};
+use crate::gameplay::movement::*;
+use crate::gameplay::player::Player;
+use crate::gameplay::projectile::*;
+use crate::gameplay::spatial::{rebuild_spatial_index, SpatialIndex};
+use crate::gameplay::stats::{PlayerStats, Stat};
+use crate::gameplay::GameplaySet;
+use crate::persistent::Mixer;
+use crate::state::{AppState, ON_EXIT_GAMEPLAY};
+
+const SPELLS_PATH: &str = "data/default.spells.json";
+/// chain lightning hits land in the tick they are cast, this only cleans up misses
+const CHAIN_HIT_LIFETIME: f32 = 0.05;
+const CHAIN_HIT_RADIUS: f32 = 8.0;
+const CHAIN_FX_TIME: f32 = 0.2;
+/// the first chain target has to be within this angle of the aim in radians
+const CHAIN_AIM_ANGLE: f32 = 1.0;
+/// spread in degrees for spells that shoot a single projectile without extra projectiles
+const UPGRADE_SPREAD: f32 = 8.0;
+
+/**
+ * Turns a projectile towards the closest enemy in range
+ */
+#[derive(Component, Deserialize, Debug, Clone, Copy)]
+pub struct Homing {
+    /// radians per second
+    pub turn_rate: f32,
+    pub range: f32,
+}
+
+#[derive(Deserialize, Debug)]
+struct SpellProjectileDef {
+    sprite: SpriteSheetDef,
+    frame_time: f32,
+    speed: f32,
+    lifetime: f32,
+    #[serde(default = "default_radius")]
+    radius: f32,
+    #[serde(default = "default_one")]
+    count: u32,
+    #[serde(default)]
+    spread: f32,
+    #[serde(default = "default_one")]
+    pierce: u32,
+    #[serde(default)]
+    homing: Option<Homing>,
+}
+
+fn default_radius() -> f32 {
+    20.0
+}
+
+fn default_one() -> u32 {
+    1
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Debug)]
+enum SpellEffectDef {
+    Projectile {
+        projectile: SpellProjectileDef,
+    },
+    Nova {
+        sprite: SpriteSheetDef,
+        frame_time: f32,
+        duration: f32,
+        #[serde(default = "default_radius")]
+        radius: f32,
+    },
+    Chain {
+        range: f32,
+        jumps: u32,
+        jump_range: f32,
+        color: [f32; 4],
+        width: f32,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+struct SpellSoundDef {
+    path: String,
+    #[serde(default = "default_volume")]
+    volume: f32,
+}
+
+#[derive(Deserialize, Debug)]
+struct SpellDef {
+    name: String,
+    cooldown: f32,
+    #[serde(default)]
+    mana: f32,
+    damage: f32,
+    effect: SpellEffectDef,
+    #[serde(default)]
+    sounds: Vec<SpellSoundDef>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpellProjectile {
+    pub sprite: SpriteSheet,
+    pub frame_time: f32,
+    pub speed: f32,
+    pub lifetime: f32,
+    /// hitbox in sprite pixels
+    pub radius: f32,
+    /// projectiles per cast
+    pub count: u32,
+    /// angle between the projectiles of a cast in degrees
+    pub spread: f32,
+    pub pierce: u32,
+    pub homing: Option<Homing>,
+}
+
+/**
+ * What a spell does when it is cast
+ */
+#[derive(Debug, Clone)]
+pub enum SpellEffect {
+    /// shoots projectiles in the aimed direction
+    Projectile { projectile: SpellProjectile },
+    /// hits everything around the player once
+    Nova {
+        sprite: SpriteSheet,
+        frame_time: f32,
+        duration: f32,
+        radius: f32,
+    },
+    /// hits the enemy closest to the aim and jumps on to the next closest ones
+    Chain {
+        range: f32,
+        jumps: u32,
+        jump_range: f32,
+        color: Color,
+        width: f32,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct SpellSound {
+    pub source: Handle<AudioSource>,
+    pub volume: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Spell {
+    pub name: String,
+    pub cooldown: f32,
+    /// mana cost per cast
+    pub mana: f32,
+    pub damage: f32,
+    pub effect: SpellEffect,
+    pub sounds: Vec<SpellSound>,
+}
+
+/**
+ * The spells of the player, the order is the order of the input bindings
+ */
+#[derive(Asset, TypePath, Debug)]
+pub struct Spells {
+    pub spells: Vec<Spell>,
+}
+
+#[derive(Default)]
+pub struct SpellsLoader;
+
+impl AssetLoader for SpellsLoader {
+    type Asset = Spells;
+    type Settings = ();
+    type Error = io::Error;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Spells, io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let defs: Vec<SpellDef> = serde_json::from_slice(&bytes)?;
+
+        let spells = defs
+            .into_iter()
+            .map(|def| Spell {
+                effect: match def.effect {
+                    SpellEffectDef::Projectile { projectile } => SpellEffect::Projectile {
+                        projectile: SpellProjectile {
+                            sprite: SpriteSheet::load(
+                                projectile.sprite,
+                                format!("{}/projectile", def.name),
+                                load_context,
+                            ),
+                            frame_time: projectile.frame_time,
+                            speed: projectile.speed,
+                            lifetime: projectile.lifetime,
+                            radius: projectile.radius,
+                            count: projectile.count,
+                            spread: projectile.spread,
+                            pierce: projectile.pierce,
+                            homing: projectile.homing,
+                        },
+                    },
+                    SpellEffectDef::Nova {
+                        sprite,
+                        frame_time,
+                        duration,
+                        radius,
+                    } => SpellEffect::Nova {
+                        sprite: SpriteSheet::load(
+                            sprite,
+                            format!("{}/nova", def.name),
+                            load_context,
+                        ),
+                        frame_time,
+                        duration,
+                        radius,
+                    },
+                    SpellEffectDef::Chain {
+                        range,
+                        jumps,
+                        jump_range,
+                        color,
+                        width,
+                    } => SpellEffect::Chain {
+                        range,
+                        jumps,
+                        jump_range,
+                        color: rgba(color),
+                        width,
+                    },
+                },
+                sounds: def
+                    .sounds
+                    .into_iter()
+                    .map(|sound| SpellSound {
+                        source: load_context.load(sound.path),
+                        volume: sound.volume,
+                    })
+                    .collect(),
+                name: def.name,
+                cooldown: def.cooldown,
+                mana: def.mana,
+                damage: def.damage,
+            })
+            .collect();
+
+        Ok(Spells { spells })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["spells.json"]
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct SpellsHandle(pub Handle<Spells>);
+
+fn load_spells(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SpellsHandle(asset_server.load(SPELLS_PATH)));
+}
+
+/**
+ * Cooldown per spell of the player
+ */
+#[derive(Component, Debug, Default)]
+pub struct Spellbook {
+    cooldowns: Vec<Timer>,
+}
+
+impl Spellbook {
+    fn tick(&mut self, spells: &Spells, delta: Duration) {
+        // the spells can finish loading after the player spawned
+        if self.cooldowns.len() != spells.spells.len() {
+            self.cooldowns = spells
+                .spells
+                .iter()
+                .map(|spell| {
+                    let mut cooldown = Timer::from_seconds(spell.cooldown, TimerMode::Once);
+                    cooldown.set_elapsed(cooldown.duration());
+                    cooldown
+                })
+                .collect();
+        }
+        for cooldown in self.cooldowns.iter_mut() {
+            cooldown.tick(delta);
+        }
+    }
+
+    /**
+     * How far the cooldown is through [0.0, 1.0], 1.0 once the spell is ready
+     */
+    pub fn cooldown_fraction(&self, spell: usize) -> f32 {
+        self.cooldowns
+            .get(spell)
+            .map(|cooldown| cooldown.fraction())
+            .unwrap_or(1.0)
+    }
+
+    pub fn ready(&self, spell: usize) -> bool {
+        self.cooldowns
+            .get(spell)
+            .map(|cooldown| cooldown.finished())
+            .unwrap_or(false)
+    }
+
+    fn start_cooldown(&mut self, spell: usize, seconds: f32) {
+        if let Some(cooldown) = self.cooldowns.get_mut(spell) {
+            cooldown.set_duration(Duration::from_secs_f32(seconds));
+            cooldown.reset();
+        }
+    }
+}
+
+/**
+ * Lightning segments and other effects that only fade out
+ */
+#[derive(Component, Debug)]
+pub(super) struct SpellFx {
+    pub(super) timer: Timer,
+}
+
+fn spawn_spell_projectile(
+    commands: &mut Commands,
+    projectile: &SpellProjectile,
+    speed: f32,
+    pierce: u32,
+    damage: f32,
+    position: Vec3,
+    direction: Vec3,
+) {
+    let animation_indices = AnimationIndices::new(0, projectile.sprite.frames.max(1) - 1);
+    let mut entity = commands.spawn(ProjectileBundle {
+        projectile: Projectile::new(ProjectileOwner::Player, damage, projectile.lifetime)
+            .with_pierce(pierce),
+        collider: Collider::circle(projectile.radius),
+        animated_sprite: AnimatedSpriteBundle {
+            sprite: SpriteBundle {
+                sprite: Sprite {
+                    color: projectile.sprite.color,
+                    ..default()
+                },
+                texture: projectile.sprite.texture.clone(),
+                transform: Transform {
+                    translation: position + direction * 32.0,
+                    scale: Vec3::splat(projectile.sprite.scale),
+                    ..default()
+                },
+                ..default()
+            },
+            atlas: TextureAtlas {
+                layout: projectile.sprite.layout.clone(),
+                index: animation_indices.first,
+            },
+            indices: animation_indices,
+            timer: AnimationTimer(Timer::from_seconds(
+                projectile.frame_time,
+                TimerMode::Repeating,
+            )),
+        },
+        moving_object: MovingObjectBundle {
+            velocity: Velocity::from_vec3(direction, speed),
+            ..default()
+        },
+    });
+    if let Some(homing) = projectile.homing {
+        entity.insert(homing);
+    }
+}
+
+fn spawn_nova(
+    commands: &mut Commands,
+    sprite: &SpriteSheet,
+    frame_time: f32,
+    duration: f32,
+    radius: f32,
+    damage: f32,
+    position: Vec3,
+) {
+    let animation_indices = AnimationIndices::new(0, sprite.frames.max(1) - 1);
+    commands.spawn(ProjectileBundle {
+        projectile: Projectile::new(ProjectileOwner::Player, damage, duration)
+            .with_pierce(u32::MAX),
+        collider: Collider::circle(radius),
+        animated_sprite: AnimatedSpriteBundle {
+            sprite: SpriteBundle {
+                sprite: Sprite {
+                    color: sprite.color,
+                    ..default()
+                },
+                texture: sprite.texture.clone(),
+                transform: Transform {
+                    translation: position,
+                    scale: Vec3::splat(sprite.scale),
+                    ..default()
+                },
+                ..default()
+            },
+            atlas: TextureAtlas {
+                layout: sprite.layout.clone(),
+                index: animation_indices.first,
+            },
+            indices: animation_indices,
+            timer: AnimationTimer(Timer::from_seconds(frame_time, TimerMode::Repeating)),
+        },
+        moving_object: MovingObjectBundle::default(),
+    });
+}
+
+/**
+ * The enemy closest to `from` within `range` that is not hit yet,
+ * optionally only within the aim angle
+ */
+fn closest_enemy(
+    index: &SpatialIndex,
+    enemy_q: &Query<&Transform, With<Enemy>>,
+    from: Vec3,
+    range: f32,
+    aim: Option<Vec3>,
+    hit: &[Entity],
+) -> Option<(Entity, Vec3)> {
+    index
+        .enemies
+        .query(from, range + MOVEMENT_MARGIN)
+        .filter(|(enemy, _)| !hit.contains(enemy))
+        .filter_map(|(enemy, _)| {
+            enemy_q
+                .get(enemy)
+                .ok()
+                .map(|transform| (enemy, transform.translation))
+        })
+        .filter(|(_, pos)| pos.distance(from) <= range)
+        .filter(|(_, pos)| match aim {
+            Some(aim) => aim.angle_between(*pos - from) <= CHAIN_AIM_ANGLE,
+            None => true,
+        })
+        .min_by(|(_, a), (_, b)| a.distance(from).total_cmp(&b.distance(from)))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_chain(
+    commands: &mut Commands,
+    index: &SpatialIndex,
+    enemy_q: &Query<&Transform, With<Enemy>>,
+    range: f32,
+    jumps: u32,
+    jump_range: f32,
+    color: Color,
+    width: f32,
+    damage: f32,
+    position: Vec3,
+    direction: Vec3,
+) {
+    let mut hit = Vec::new();
+    let mut from = position;
+    let mut target = closest_enemy(index, enemy_q, from, range, Some(direction), &hit);
+    while let Some((enemy, pos)) = target {
+        hit.push(enemy);
+        commands.spawn((
+            Projectile::new(ProjectileOwner::Player, damage, CHAIN_HIT_LIFETIME),
+            Collider::circle(CHAIN_HIT_RADIUS),
+            TransformBundle::from_transform(Transform::from_translation(pos)),
+        ));
+
+        let segment = pos - from;
+        commands.spawn((
+            SpellFx {
+                timer: Timer::from_seconds(CHAIN_FX_TIME, TimerMode::Once),
+            },
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::new(segment.length(), width)),
+                    ..default()
+                },
+                transform: Transform {
+                    translation: from + segment / 2.0,
+                    rotation: Quat::from_rotation_z(segment.y.atan2(segment.x)),
+                    ..default()
+                },
+                ..default()
+            },
+        ));
+
+        if hit.len() > jumps as usize {
+            break;
+        }
+        from = pos;
+        target = closest_enemy(index, enemy_q, from, jump_range, None, &hit);
+    }
+}
+
+fn spell_sound_fx(commands: &mut Commands, sounds: &[SpellSound], mixer: &Mixer) {
+    for sound in sounds.iter() {
+        commands.spawn(AudioBundle {
+            source: sound.source.clone(),
+            settings: PlaybackSettings {
+                mode: PlaybackMode::Despawn,
+                volume: mixer.as_volume_with_multiplier(sound.volume),
+                ..default()
+            },
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cast_spell(
+    mut commands: Commands,
+    mut player_q: Query<(&mut Spellbook, &mut Mana, &PlayerStats, &Transform), With<Player>>,
+    enemy_q: Query<&Transform, With<Enemy>>,
+    index: Res<SpatialIndex>,
+    spells: Res<Assets<Spells>>,
+    spells_handle: Res<SpellsHandle>,
+    input: Res<PlayerInput>,
+    time: Res<Time>,
+    #[cfg(not(feature = "storage"))] mixer: Res<Mixer>,
+    #[cfg(feature = "storage")] mixer: Res<bevy_persistent::Persistent<Mixer>>,
+) {
+    let spells = match spells.get(&spells_handle.0) {
+        Some(spells) => spells,
+        None => return,
+    };
+    let (mut spellbook, mut mana, stats, player_transform) = match player_q.get_single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    spellbook.tick(spells, time.delta());
+
+    let direction = match input.fire {
+        Some(direction) => direction.extend(0.0),
+        None => return,
+    };
+    let spell = match spells.spells.get(input.spell) {
+        Some(spell) => spell,
+        None => return,
+    };
+    if !spellbook.ready(input.spell) || !mana.spend(spell.mana) {
+        return;
+    }
+    spellbook.start_cooldown(input.spell, spell.cooldown * stats.get(Stat::Cooldown));
+
+    let position = player_transform.translation;
+    let damage = spell.damage * stats.get(Stat::Damage);
+    match &spell.effect {
+        SpellEffect::Projectile { projectile } => {
+            let count = projectile.count.max(1) + stats.get(Stat::ProjectileCount).round() as u32;
+            let spread = if projectile.spread > 0.0 {
+                projectile.spread
+            } else {
+                UPGRADE_SPREAD
+            };
+            for i in 0..count {
+                let angle = (i as f32 - (count - 1) as f32 / 2.0) * spread.to_radians();
+                spawn_spell_projectile(
+                    &mut commands,
+                    projectile,
+                    projectile.speed * stats.get(Stat::ProjectileSpeed),
+                    projectile
+                        .pierce
+                        .saturating_add(stats.get(Stat::Pierce).round() as u32),
+                    damage,
+                    position,
+                    Quat::from_rotation_z(angle) * direction,
+                );
+            }
+        }
+        SpellEffect::Nova {
+            sprite,
+            frame_time,
+            duration,
+            radius,
+        } => spawn_nova(
+            &mut commands,
+            sprite,
+            *frame_time,
+            *duration,
+            *radius,
+            damage,
+            position,
+        ),
+        SpellEffect::Chain {
+            range,
+            jumps,
+            jump_range,
+            color,
+            width,
+        } => spawn_chain(
+            &mut commands,
+            &index,
+            &enemy_q,
+            *range,
+            *jumps,
+            *jump_range,
+            *color,
+            *width,
+            damage,
+            position,
+            direction,
+        ),
+    }
+    spell_sound_fx(&mut commands, &spell.sounds, &mixer);
+}
+
+fn steer_homing(
+    mut projectile_q: Query<(&Homing, &Transform, &mut Velocity)>,
+    index: Res<SpatialIndex>,
+    time: Res<Time>,
+) {
+    for (homing, transform, mut velocity) in projectile_q.iter_mut() {
+        let pos = transform.translation;
+        let target = index
+            .enemies
+            .query(pos, homing.range)
+            .map(|(_, enemy_pos)| enemy_pos)
+            .min_by(|a, b| a.distance(pos).total_cmp(&b.distance(pos)));
+        let target = match target {
+            Some(target) => target,
+            None => continue,
+        };
+
+        let current = velocity.direction.xy();
+        let wanted = (target - pos).xy();
+        let angle = current.angle_between(wanted);
+        if !angle.is_finite() {
+            continue;
+        }
+        let max_turn = homing.turn_rate * time.delta_seconds();
+        let turn = angle.clamp(-max_turn, max_turn);
+        velocity.direction = Vec2::from_angle(turn).rotate(current).extend(0.0);
+    }
+}
+
+fn fade_spell_fx(
+    mut commands: Commands,
+    mut fx_q: Query<(Entity, &mut SpellFx, &mut Sprite)>,
+    time: Res<Time>,
+) {
+    for (entity, mut fx, mut sprite) in fx_q.iter_mut() {
+        fx.timer.tick(time.delta());
+        if fx.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        sprite.color.set_alpha(fx.timer.fraction_remaining());
+    }
+}
+
+fn despawn_spell_fx(mut commands: Commands, fx_q: Query<Entity, With<SpellFx>>) {
+    for entity in fx_q.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+pub struct SpellPlugin;
+
+impl Plugin for SpellPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Spells>()
+            .init_asset_loader::<SpellsLoader>()
+            .add_systems(Startup, load_spells)
+            .add_systems(
+                FixedUpdate,
+                (
+                    steer_homing
+                        .after(rebuild_spatial_index)
+                        .in_set(GameplaySet::Steering),
+                    cast_spell
+                        .after(projectile_out_of_bounds)
+                        .after(regenerate_mana)
+                        .before(detect_collisions)
+                        .in_set(GameplaySet::Combat),
+                ),
+            )
+            .add_systems(Update, fade_spell_fx.run_if(in_state(AppState::InGame)))
+            .add_systems(ON_EXIT_GAMEPLAY, despawn_spell_fx);
+    }
+}
@@ -0,0 +1,219 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::gameplay::health::Health;
+use crate::gameplay::GameplaySet;
+
+const PLAYER_MOVE_SPEED: f32 = 175.0;
+const PLAYER_SPRINT_MULTIPLIER: f32 = 2.0;
+const PLAYER_MAX_HEALTH: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stat {
+    MoveSpeed,
+    SprintMultiplier,
+    MaxHealth,
+    /// multiplier on the spell cooldowns
+    Cooldown,
+    /// multiplier on the spell projectile speed
+    ProjectileSpeed,
+    /// multiplier on the spell damage
+    Damage,
+    /// projectiles per cast on top of the ones of the spell
+    ProjectileCount,
+    /// enemies a projectile passes on top of the pierce of the spell
+    Pierce,
+}
+
+impl Stat {
+    /// in declaration order, so a stat indexes its own slot
+    pub const ALL: &'static [Stat] = &[
+        Stat::MoveSpeed,
+        Stat::SprintMultiplier,
+        Stat::MaxHealth,
+        Stat::Cooldown,
+        Stat::ProjectileSpeed,
+        Stat::Damage,
+        Stat::ProjectileCount,
+        Stat::Pierce,
+    ];
+
+    /**
+     * The slot of the stat, a new variant doesn't compile until it has one
+     */
+    pub const fn index(self) -> usize {
+        match self {
+            Stat::MoveSpeed => 0,
+            Stat::SprintMultiplier => 1,
+            Stat::MaxHealth => 2,
+            Stat::Cooldown => 3,
+            Stat::ProjectileSpeed => 4,
+            Stat::Damage => 5,
+            Stat::ProjectileCount => 6,
+            Stat::Pierce => 7,
+        }
+    }
+}
+
+const STAT_COUNT: usize = Stat::ALL.len();
+
+// ALL lists every stat exactly once, at its own index
+const _: () = {
+    assert!(STAT_COUNT == Stat::Pierce as usize + 1);
+    let mut i = 0;
+    while i < STAT_COUNT {
+        assert!(Stat::ALL[i].index() == i);
+        i += 1;
+    }
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModifierKind {
+    Add(f32),
+    Multiply(f32),
+}
+
+#[derive(Debug, Clone)]
+pub struct StatModifier {
+    pub stat: Stat,
+    pub kind: ModifierKind,
+    /// permanent without one
+    pub duration: Option<Timer>,
+}
+
+impl StatModifier {
+    pub fn add(stat: Stat, amount: f32) -> Self {
+        Self {
+            stat,
+            kind: ModifierKind::Add(amount),
+            duration: None,
+        }
+    }
+
+    pub fn multiply(stat: Stat, factor: f32) -> Self {
+        Self {
+            stat,
+            kind: ModifierKind::Multiply(factor),
+            duration: None,
+        }
+    }
+
+    pub fn with_duration(mut self, seconds: f32) -> Self {
+        self.duration = Some(Timer::from_seconds(seconds, TimerMode::Once));
+        self
+    }
+}
+
+/**
+ * Base values of the player and the modifiers stacked on top of them.
+ * A stat is (base + all additions) * all multipliers.
+ */
+#[derive(Component, Debug, Clone)]
+pub struct PlayerStats {
+    base: [f32; STAT_COUNT],
+    modifiers: Vec<StatModifier>,
+}
+
+impl Default for PlayerStats {
+    fn default() -> Self {
+        let mut stats = Self {
+            base: [0.0; STAT_COUNT],
+            modifiers: Vec::new(),
+        };
+        stats.set_base(Stat::MoveSpeed, PLAYER_MOVE_SPEED);
+        stats.set_base(Stat::SprintMultiplier, PLAYER_SPRINT_MULTIPLIER);
+        stats.set_base(Stat::MaxHealth, PLAYER_MAX_HEALTH);
+        stats.set_base(Stat::Cooldown, 1.0);
+        stats.set_base(Stat::ProjectileSpeed, 1.0);
+        stats.set_base(Stat::Damage, 1.0);
+        stats
+    }
+}
+
+impl PlayerStats {
+    pub fn base(&self, stat: Stat) -> f32 {
+        self.base[stat.index()]
+    }
+
+    pub fn set_base(&mut self, stat: Stat, value: f32) {
+        self.base[stat.index()] = value;
+    }
+
+    pub fn get(&self, stat: Stat) -> f32 {
+        let (add, multiply) = self
+            .modifiers
+            .iter()
+            .filter(|modifier| modifier.stat == stat)
+            .fold((0.0, 1.0), |(add, multiply), modifier| {
+                match modifier.kind {
+                    ModifierKind::Add(amount) => (add + amount, multiply),
+                    ModifierKind::Multiply(factor) => (add, multiply * factor),
+                }
+            });
+        (self.base(stat) + add) * multiply
+    }
+
+    pub fn add_modifier(&mut self, modifier: StatModifier) {
+        self.modifiers.push(modifier);
+    }
+
+    fn tick(&mut self, delta: Duration) {
+        self.modifiers
+            .retain_mut(|modifier| match modifier.duration.as_mut() {
+                Some(duration) => !duration.tick(delta).finished(),
+                None => true,
+            });
+    }
+}
+
+pub(super) fn tick_stat_modifiers(mut stats_q: Query<&mut PlayerStats>, time: Res<Time>) {
+    for mut stats in stats_q.iter_mut() {
+        stats.tick(time.delta());
+    }
+}
+
+/**
+ * Keeps the health in line with the max health stat, gained max health is healed
+ */
+pub(super) fn apply_max_health(mut stats_q: Query<(&PlayerStats, &mut Health)>) {
+    for (stats, mut health) in stats_q.iter_mut() {
+        let max_health = stats.get(Stat::MaxHealth);
+        if max_health == health.max_health {
+            continue;
+        }
+        let gained = (max_health - health.max_health).max(0.0);
+        health.max_health = max_health;
+        health.health = (health.health + gained).min(max_health);
+    }
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (tick_stat_modifiers, apply_max_health)
+                .chain()
+                .in_set(GameplaySet::Steering),
+        );
+    }
+}
@@ -0,0 +1,459 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::f32::consts::TAU;
+use std::io;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use rand_core::RngCore;
+use serde::Deserialize;
+
+use crate::ext::{FRng, IntoVec3, RandomAround};
+use crate::gameplay::archetype::{EnemyArchetypes, EnemyArchetypesHandle};
+use crate::gameplay::boss::Boss;
+use crate::gameplay::enemy::{spawn_enemy, Enemy};
+use crate::gameplay::navigation::{update_flow_field, FlowField};
+use crate::gameplay::player::Player;
+use crate::gameplay::GameplaySet;
+use crate::ldtk::{CollisionMap, LevelBounds, SpawnPoints};
+use crate::seed::RunRng;
+use crate::state::ON_ENTER_GAMEPLAY;
+
+const WAVES_PATH: &str = "data/default.waves.json";
+/// random positions tried before falling back to a spawner
+const SPAWN_ATTEMPTS: u32 = 16;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WaveGroup {
+    /// archetype name, picked by weight when missing
+    #[serde(default)]
+    pub enemy: Option<String>,
+    pub count: u32,
+}
+
+/**
+ * Where the enemies of a wave appear
+ */
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub enum SpawnPattern {
+    /// around the EnemySpawner entities of the level
+    #[default]
+    Spawners,
+    /// anywhere between two distances from the player
+    Around { min_radius: f32, max_radius: f32 },
+    /// evenly spaced on a circle around the player
+    Ring { radius: f32 },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Wave {
+    pub groups: Vec<WaveGroup>,
+    #[serde(default)]
+    pub pattern: SpawnPattern,
+    /// seconds between two spawns, 0 spawns the whole wave at once
+    #[serde(default)]
+    pub interval: f32,
+    /// multiplier of the archetype speed
+    pub speed_scale: f32,
+}
+
+impl Wave {
+    fn count(&self) -> u32 {
+        self.groups.iter().map(|group| group.count).sum()
+    }
+}
+
+/**
+ * Distances from the player for enemies of levels without spawners
+ */
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct FallbackSpawn {
+    pub min_radius: f32,
+    pub max_radius: f32,
+}
+
+/**
+ * How the last wave grows when it is repeated after all waves are done
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct Endless {
+    pub count_growth: f32,
+    pub speed_growth: f32,
+    pub max_speed_scale: f32,
+}
+
+/**
+ * A boss shows up every `every_kills` kills of other enemies
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct BossSchedule {
+    pub enemy: String,
+    pub every_kills: u32,
+    pub min_radius: f32,
+    pub max_radius: f32,
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct WaveList {
+    /// seconds before the first wave
+    pub first_breather: f32,
+    /// seconds between a cleared wave and the next one
+    pub breather: f32,
+    pub waves: Vec<Wave>,
+    pub fallback_spawn: FallbackSpawn,
+    pub endless: Endless,
+    #[serde(default)]
+    pub boss: Option<BossSchedule>,
+}
+
+impl WaveList {
+    fn wave(&self, index: usize) -> Option<Wave> {
+        let last_index = self.waves.len().checked_sub(1)?;
+        let mut wave = self.waves[index.min(last_index)].clone();
+        let repeats = index.saturating_sub(last_index) as f32;
+        if repeats > 0.0 {
+            for group in wave.groups.iter_mut() {
+                group.count =
+                    (group.count as f32 * (1.0 + self.endless.count_growth * repeats)) as u32;
+            }
+            wave.speed_scale = (wave.speed_scale + self.endless.speed_growth * repeats)
+                .min(self.endless.max_speed_scale.max(wave.speed_scale));
+        }
+        Some(wave)
+    }
+}
+
+#[derive(Default)]
+pub struct WaveListLoader;
+
+impl AssetLoader for WaveListLoader {
+    type Asset = WaveList;
+    type Settings = ();
+    type Error = io::Error;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<WaveList, io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.json"]
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct WaveListHandle(pub Handle<WaveList>);
+
+#[derive(Event, Debug)]
+pub struct WaveStarted {
+    pub number: u32,
+}
+
+#[derive(Event, Debug)]
+pub struct WaveCleared {
+    pub number: u32,
+}
+
+#[derive(Debug, Default)]
+enum DirectorState {
+    /// waiting for the wave list and the enemy archetypes to load
+    #[default]
+    Starting,
+    Breather(Timer),
+    Spawning {
+        wave: Wave,
+        /// enemy kinds that still have to spawn, in spawn order
+        queue: Vec<Option<String>>,
+        spawned: u32,
+        /// spawns that were due but found no valid position yet
+        overdue: usize,
+        timer: Timer,
+        ring_angle: f32,
+    },
+    /// everything is spawned, waiting for the player to clear the wave
+    Fighting,
+}
+
+/**
+ * Runs the waves of enemies of a run
+ */
+#[derive(Resource, Debug, Default)]
+pub struct WaveDirector {
+    /// the current wave counting from 1, 0 before the first wave
+    pub number: u32,
+    state: DirectorState,
+}
+
+fn load_waves(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(WaveListHandle(asset_server.load(WAVES_PATH)));
+}
+
+fn reset_director(mut director: ResMut<WaveDirector>) {
+    *director = WaveDirector::default();
+}
+
+/**
+ * Where enemies may appear: inside the level, off solid cells and with a path to the player
+ */
+#[derive(SystemParam)]
+pub(super) struct SpawnArea<'w> {
+    bounds: Res<'w, LevelBounds>,
+    collision_map: Res<'w, CollisionMap>,
+    flow_field: Res<'w, FlowField>,
+    spawn_points: Res<'w, SpawnPoints>,
+}
+
+impl SpawnArea<'_> {
+    fn is_valid(&self, pos: Vec3) -> bool {
+        self.bounds.contains(&pos)
+            && !self.collision_map.is_solid_at(pos.xy())
+            && self.flow_field.reachable(&self.collision_map, pos)
+    }
+
+    /**
+     * Resamples until a valid position comes up, a valid spawner after too many attempts.
+     * `None` if there is neither, the caller tries again next tick.
+     */
+    pub(super) fn find(
+        &self,
+        rng: &mut RunRng,
+        mut sample: impl FnMut(&mut RunRng, u32) -> Vec3,
+    ) -> Option<Vec3> {
+        for attempt in 0..SPAWN_ATTEMPTS {
+            let pos = sample(rng, attempt);
+            if self.is_valid(pos) {
+                return Some(pos);
+            }
+        }
+        let spawners: Vec<Vec3> = self
+            .spawn_points
+            .enemy_spawners
+            .iter()
+            .map(|spawner| spawner.pos.xyz())
+            .filter(|pos| self.is_valid(*pos))
+            .collect();
+        if spawners.is_empty() {
+            return None;
+        }
+        Some(spawners[rng.enemy_spawn.next_u32() as usize % spawners.len()])
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_position(
+    pattern: SpawnPattern,
+    fallback: FallbackSpawn,
+    spawned: u32,
+    count: u32,
+    ring_angle: f32,
+    player_pos: Vec3,
+    area: &SpawnArea,
+    rng: &mut RunRng,
+) -> Option<Vec3> {
+    let spawners = &area.spawn_points.enemy_spawners;
+    area.find(rng, |rng, attempt| match pattern {
+        SpawnPattern::Spawners if !spawners.is_empty() => {
+            let index = rng.enemy_spawn.next_u32() as usize % spawners.len();
+            let spawner = spawners[index];
+            spawner
+                .pos
+                .xyz()
+                .random_around(&mut rng.enemy_spawn, 0.0, spawner.radius)
+        }
+        // levels without spawners fall back to spawning around the player
+        SpawnPattern::Spawners => player_pos.random_around(
+            &mut rng.enemy_spawn,
+            fallback.min_radius,
+            fallback.max_radius,
+        ),
+        SpawnPattern::Around {
+            min_radius,
+            max_radius,
+        } => player_pos.random_around(&mut rng.enemy_spawn, min_radius, max_radius),
+        SpawnPattern::Ring { radius } => {
+            // blocked places of the ring are moved to a random angle
+            let angle = if attempt == 0 {
+                ring_angle + spawned as f32 * TAU / count.max(1) as f32
+            } else {
+                rng.enemy_spawn.next_f32() * TAU
+            };
+            player_pos + Vec3::new(angle.cos(), angle.sin(), 0.0) * radius
+        }
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn run_director(
+    mut commands: Commands,
+    mut director: ResMut<WaveDirector>,
+    wave_lists: Res<Assets<WaveList>>,
+    wave_list_handle: Res<WaveListHandle>,
+    archetypes: Res<Assets<EnemyArchetypes>>,
+    archetypes_handle: Res<EnemyArchetypesHandle>,
+    // a boss doesn't hold back the next wave
+    enemy_q: Query<(), (With<Enemy>, Without<Boss>)>,
+    player_q: Query<&Transform, With<Player>>,
+    spawn_area: SpawnArea,
+    mut rng: ResMut<RunRng>,
+    time: Res<Time>,
+    mut evw_wave_started: EventWriter<WaveStarted>,
+    mut evw_wave_cleared: EventWriter<WaveCleared>,
+) {
+    let wave_list = match wave_lists.get(&wave_list_handle.0) {
+        Some(wave_list) => wave_list,
+        None => return,
+    };
+    let archetypes = match archetypes.get(&archetypes_handle.0) {
+        Some(archetypes) => archetypes,
+        None => return,
+    };
+    let player_pos = match player_q.get_single() {
+        Ok(player_transform) => player_transform.translation,
+        Err(_) => return,
+    };
+
+    let number = director.number;
+    match &mut director.state {
+        DirectorState::Starting => {
+            director.state = DirectorState::Breather(Timer::from_seconds(
+                wave_list.first_breather,
+                TimerMode::Once,
+            ));
+        }
+        DirectorState::Breather(timer) => {
+            if !timer.tick(time.delta()).finished() {
+                return;
+            }
+            let wave = match wave_list.wave(number as usize) {
+                Some(wave) => wave,
+                None => return,
+            };
+            let queue = wave
+                .groups
+                .iter()
+                .flat_map(|group| std::iter::repeat_n(group.enemy.clone(), group.count as usize))
+                .rev()
+                .collect();
+            let mut timer = Timer::from_seconds(wave.interval, TimerMode::Repeating);
+            // the first enemy spawns right away
+            timer.tick(timer.duration());
+            director.number += 1;
+            director.state = DirectorState::Spawning {
+                queue,
+                spawned: 0,
+                overdue: 0,
+                timer,
+                ring_angle: rng.enemy_spawn.next_f32() * TAU,
+                wave,
+            };
+            evw_wave_started.send(WaveStarted {
+                number: director.number,
+            });
+        }
+        DirectorState::Spawning {
+            wave,
+            queue,
+            spawned,
+            overdue,
+            timer,
+            ring_angle,
+        } => {
+            let due = if wave.interval > 0.0 {
+                timer.tick(time.delta());
+                *overdue + timer.times_finished_this_tick() as usize
+            } else {
+                queue.len()
+            };
+            *overdue = 0;
+            let count = wave.count();
+            for placed in 0..due {
+                let kind = match queue.last() {
+                    Some(kind) => kind.clone(),
+                    None => break,
+                };
+                let translation = match spawn_position(
+                    wave.pattern,
+                    wave_list.fallback_spawn,
+                    *spawned,
+                    count,
+                    *ring_angle,
+                    player_pos,
+                    &spawn_area,
+                    &mut rng,
+                ) {
+                    Some(translation) => translation,
+                    None => {
+                        *overdue = due - placed;
+                        break;
+                    }
+                };
+                queue.pop();
+                spawn_enemy(
+                    &mut commands,
+                    archetypes,
+                    kind.as_deref(),
+                    &mut rng,
+                    translation,
+                    wave.speed_scale,
+                );
+                *spawned += 1;
+            }
+            if queue.is_empty() {
+                director.state = DirectorState::Fighting;
+            }
+        }
+        DirectorState::Fighting => {
+            if enemy_q.is_empty() {
+                evw_wave_cleared.send(WaveCleared { number });
+                director.state = DirectorState::Breather(Timer::from_seconds(
+                    wave_list.breather,
+                    TimerMode::Once,
+                ));
+            }
+        }
+    }
+}
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<WaveList>()
+            .init_asset_loader::<WaveListLoader>()
+            .init_resource::<WaveDirector>()
+            .add_event::<WaveStarted>()
+            .add_event::<WaveCleared>()
+            .add_systems(Startup, load_waves)
+            .add_systems(ON_ENTER_GAMEPLAY, reset_director)
+            .add_systems(
+                FixedUpdate,
+                run_director
+                    .after(update_flow_field)
+                    .in_set(GameplaySet::Steering),
+            );
+    }
+}
@@ -18,41 +18,402 @@
 
 use bevy::prelude::*;
 
+use crate::gameplay::anim::*;
 use crate::gameplay::overlap::StaticObject;
+use crate::state::AppState;
+
+pub mod project;
+
+use project::{LdtkEntity, LdtkLayer, LdtkLevel, LdtkLoader, LdtkProject};
 
 const Z_INDEX: f32 = -100.0;
+const ENEMY_SPAWNER_RADIUS: f32 = 256.0;
+
+const PROJECT_PATH: &str = "levels/Untitled.ldtk";
+const DEFAULT_LEVEL: &str = "Level_0";
+const COLLISION_LAYER: &str = "Collision";
 
-const MIN_POS: Vec2 = Vec2::new(-1640.0, 1693.0);
-const MAX_POS: Vec2 = Vec2::new(1560.0, -1660.5);
+#[derive(Resource, Debug)]
+pub struct LdtkProjectHandle(pub Handle<LdtkProject>);
+
+/**
+ * The identifier of the level that is spawned, changing it swaps the level
+ */
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct LevelSelection(pub String);
 
-pub fn pos_inside_level(pos: &Vec3) -> bool {
-    pos.x >= MIN_POS.x && pos.x <= MAX_POS.x && pos.y >= MAX_POS.y && pos.y <= MIN_POS.y
+impl Default for LevelSelection {
+    fn default() -> Self {
+        Self(DEFAULT_LEVEL.to_string())
+    }
 }
 
-fn spawn_level(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let ground = SpriteBundle {
-        texture: asset_server.load("levels/Untitled/png/Level_0__Ground.png"),
-        transform: Transform::from_translation(Vec3::new(0.0, 0.0, Z_INDEX)),
-        ..default()
-    };
+#[derive(Component, Debug)]
+struct LevelRoot;
+
+/**
+ * The playable area of the spawned level in world coordinates
+ */
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct LevelBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl LevelBounds {
+    fn from_level(level: &LdtkLevel) -> Self {
+        let half_size = level.size() / 2.0;
+        Self {
+            min: -half_size,
+            max: half_size,
+        }
+    }
+
+    pub fn contains(&self, pos: &Vec3) -> bool {
+        pos.x >= self.min.x && pos.x <= self.max.x && pos.y >= self.min.y && pos.y <= self.max.y
+    }
+}
+
+/**
+ * Where things start in the spawned level, placed in its Entities layer
+ */
+#[derive(Resource, Debug, Clone)]
+pub struct SpawnPoints {
+    pub player: Vec2,
+    pub cat: Vec2,
+    pub enemy_spawners: Vec<EnemySpawner>,
+}
 
-    let rock = SpriteBundle {
-        texture: asset_server.load("levels/Untitled/png/Level_0__Rock.png"),
-        transform: Transform::from_translation(Vec3::new(0.0, 0.0, 1.0)),
-        ..default()
+impl Default for SpawnPoints {
+    fn default() -> Self {
+        Self {
+            player: Vec2::ZERO,
+            cat: Vec2::new(-55.0, 55.0),
+            enemy_spawners: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EnemySpawner {
+    pub pos: Vec2,
+    pub radius: f32,
+}
+
+#[derive(Component, Debug)]
+pub struct LevelProp;
+
+/**
+ * The solid cells of the level, taken from its Collision IntGrid layer
+ */
+#[derive(Resource, Debug, Default)]
+pub struct CollisionMap {
+    /// world position of the top left corner
+    origin: Vec2,
+    grid_size: f32,
+    columns: i32,
+    rows: i32,
+    solid: Vec<bool>,
+}
+
+impl CollisionMap {
+    fn from_layer(level: &LdtkLevel, layer: &LdtkLayer) -> Self {
+        Self {
+            origin: level.to_world(layer.offset()),
+            grid_size: layer.grid_size as f32,
+            columns: layer.c_wid,
+            rows: layer.c_hei,
+            solid: layer.int_grid_csv.iter().map(|value| *value != 0).collect(),
+        }
+    }
+
+    pub fn columns(&self) -> i32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> i32 {
+        self.rows
+    }
+
+    pub fn grid_size(&self) -> f32 {
+        self.grid_size
+    }
+
+    pub fn contains(&self, cell: IVec2) -> bool {
+        cell.x >= 0 && cell.y >= 0 && cell.x < self.columns && cell.y < self.rows
+    }
+
+    pub fn cell(&self, pos: Vec2) -> IVec2 {
+        IVec2::new(
+            ((pos.x - self.origin.x) / self.grid_size).floor() as i32,
+            ((self.origin.y - pos.y) / self.grid_size).floor() as i32,
+        )
+    }
+
+    pub fn cell_center(&self, cell: IVec2) -> Vec2 {
+        Vec2::new(
+            self.origin.x + (cell.x as f32 + 0.5) * self.grid_size,
+            self.origin.y - (cell.y as f32 + 0.5) * self.grid_size,
+        )
+    }
+
+    /**
+     * Cells outside of the layer are never solid, the level bounds take care of those
+     */
+    pub fn is_solid(&self, cell: IVec2) -> bool {
+        if !self.contains(cell) {
+            return false;
+        }
+        self.solid
+            .get((cell.y * self.columns + cell.x) as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub fn is_solid_at(&self, pos: Vec2) -> bool {
+        self.is_solid(self.cell(pos))
+    }
+
+    /**
+     * Whether the straight line between two points crosses no solid cell
+     */
+    pub fn line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+        if self.solid.is_empty() {
+            return true;
+        }
+        let steps = (from.distance(to) / (self.grid_size * 0.25))
+            .ceil()
+            .max(1.0) as i32;
+        (0..=steps).all(|step| !self.is_solid_at(from.lerp(to, step as f32 / steps as f32)))
+    }
+
+    /**
+     * Whether a box around `center` touches any solid cell
+     */
+    pub fn overlaps(&self, center: Vec2, half_size: Vec2) -> bool {
+        if self.solid.is_empty() {
+            return false;
+        }
+        let min = self.cell(Vec2::new(center.x - half_size.x, center.y + half_size.y));
+        let max = self.cell(Vec2::new(center.x + half_size.x, center.y - half_size.y));
+        (min.y..=max.y).any(|y| (min.x..=max.x).any(|x| self.is_solid(IVec2::new(x, y))))
+    }
+}
+
+fn load_project(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(LdtkProjectHandle(asset_server.load(PROJECT_PATH)));
+}
+
+fn despawn_level(
+    mut commands: Commands,
+    root_q: Query<Entity, With<LevelRoot>>,
+    prop_q: Query<Entity, With<LevelProp>>,
+) {
+    for entity in root_q.iter().chain(prop_q.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<LevelBounds>();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_level(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    project_handle: Res<LdtkProjectHandle>,
+    projects: Res<Assets<LdtkProject>>,
+    selection: Res<LevelSelection>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    // the selection last reported as missing, this runs every frame until a level spawns
+    mut missing: Local<Option<String>>,
+) {
+    let project = match projects.get(&project_handle.0) {
+        Some(project) => project,
+        None => return,
     };
+    let level = match project.level(&selection.0) {
+        Some(level) => level,
+        None => {
+            if missing.as_ref() != Some(&selection.0) {
+                error!("level {} not found in {}", selection.0, PROJECT_PATH);
+                *missing = Some(selection.0.clone());
+            }
+            if *state.get() != AppState::MainMenu {
+                next_state.set(AppState::MainMenu);
+            }
+            return;
+        }
+    };
+    *missing = None;
+
+    commands.insert_resource(LevelBounds::from_level(level));
+    match level
+        .layers()
+        .iter()
+        .find(|layer| layer.identifier == COLLISION_LAYER && layer.layer_type == "IntGrid")
+    {
+        Some(layer) => commands.insert_resource(CollisionMap::from_layer(level, layer)),
+        None => {
+            warn!("level {} has no {} layer", selection.0, COLLISION_LAYER);
+            commands.insert_resource(CollisionMap::default());
+        }
+    }
+    spawn_tiles(&mut commands, project, level);
+
+    let mut spawn_points = SpawnPoints::default();
+    for (pos, entity) in level.entities() {
+        match entity.identifier.as_str() {
+            "PlayerStart" => spawn_points.player = pos,
+            "CatStart" => spawn_points.cat = pos,
+            "EnemySpawner" => spawn_points.enemy_spawners.push(EnemySpawner {
+                pos,
+                radius: entity.fields.f32("radius").unwrap_or(ENEMY_SPAWNER_RADIUS),
+            }),
+            "Prop" => spawn_prop(
+                &mut commands,
+                &asset_server,
+                &mut texture_atlas_layouts,
+                project,
+                pos,
+                entity,
+            ),
+            identifier => warn!("unknown LDtk entity {}", identifier),
+        }
+    }
+    commands.insert_resource(spawn_points);
+}
 
+fn spawn_tiles(commands: &mut Commands, project: &LdtkProject, level: &LdtkLevel) {
     commands
-        .spawn((StaticObject, ground))
+        .spawn((
+            LevelRoot,
+            StaticObject,
+            SpatialBundle::from_transform(Transform::from_translation(Vec3::new(
+                0.0, 0.0, Z_INDEX,
+            ))),
+        ))
         .with_children(|parent| {
-            parent.spawn((StaticObject, rock));
+            // layer instances are listed from top to bottom
+            for (depth, layer) in level.layers().iter().rev().enumerate() {
+                let tileset = match layer
+                    .tileset_def_uid
+                    .and_then(|uid| project.tilesets.get(&uid))
+                {
+                    Some(tileset) => tileset,
+                    None => continue,
+                };
+
+                let half_tile = layer.grid_size as f32 / 2.0;
+                let visibility = if layer.visible {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                };
+
+                parent
+                    .spawn((
+                        StaticObject,
+                        Name::new(layer.identifier.clone()),
+                        SpatialBundle {
+                            transform: Transform::from_translation(Vec3::new(
+                                0.0,
+                                0.0,
+                                depth as f32,
+                            )),
+                            visibility,
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        for tile in layer.tiles() {
+                            let px = Vec2::new(tile.px[0] as f32, tile.px[1] as f32)
+                                + layer.offset()
+                                + half_tile;
+                            parent.spawn((
+                                StaticObject,
+                                SpriteBundle {
+                                    texture: tileset.texture.clone(),
+                                    sprite: Sprite {
+                                        color: Color::srgba(1.0, 1.0, 1.0, layer.opacity),
+                                        flip_x: tile.flip_x(),
+                                        flip_y: tile.flip_y(),
+                                        ..default()
+                                    },
+                                    transform: Transform::from_translation(
+                                        level.to_world(px).extend(0.0),
+                                    ),
+                                    ..default()
+                                },
+                                TextureAtlas {
+                                    layout: tileset.layout.clone(),
+                                    index: tile.t as usize,
+                                },
+                            ));
+                        }
+                    });
+            }
         });
 }
 
+fn spawn_prop(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    texture_atlas_layouts: &mut ResMut<Assets<TextureAtlasLayout>>,
+    project: &LdtkProject,
+    pos: Vec2,
+    entity: &LdtkEntity,
+) {
+    let texture = match entity
+        .fields
+        .str("sprite")
+        .and_then(|path| project.resolve(path))
+    {
+        Some(path) => asset_server.load(path),
+        None => {
+            warn!("prop at {} has no sprite", pos);
+            return;
+        }
+    };
+    let frames = entity.fields.i32("frames").unwrap_or(1).max(1) as usize;
+    let layout =
+        TextureAtlasLayout::from_grid(entity.size().as_uvec2(), frames as u32, 1, None, None);
+
+    commands.spawn((
+        LevelProp,
+        AnimatedSpriteBundle {
+            sprite: SpriteBundle {
+                texture,
+                transform: Transform::from_translation(pos.extend(-pos.y / 1000.0)),
+                ..default()
+            },
+            indices: AnimationIndices::new(0, frames - 1),
+            timer: AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
+            atlas: TextureAtlas {
+                layout: texture_atlas_layouts.add(layout),
+                index: 0,
+            },
+        },
+    ));
+}
+
 pub struct LdtkPlugin;
 
 impl Plugin for LdtkPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_level);
+        app.init_asset::<LdtkProject>()
+            .init_asset_loader::<LdtkLoader>()
+            .init_resource::<LevelSelection>()
+            .init_resource::<SpawnPoints>()
+            .init_resource::<CollisionMap>()
+            .add_systems(Startup, load_project)
+            .add_systems(
+                Update,
+                (
+                    despawn_level.run_if(resource_changed::<LevelSelection>),
+                    spawn_level.run_if(not(resource_exists::<LevelBounds>)),
+                )
+                    .chain(),
+            );
     }
 }
@@ -0,0 +1,323 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::io;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AssetPath, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::Deserialize;
+
+/**
+ * A parsed LDtk project with the tilesets it references
+ */
+#[derive(Asset, TypePath, Debug)]
+pub struct LdtkProject {
+    pub levels: Vec<LdtkLevel>,
+    pub tilesets: HashMap<i32, LdtkTileset>,
+    path: AssetPath<'static>,
+}
+
+impl LdtkProject {
+    /**
+     * Resolves a path stored in the project, which is relative to the project file
+     */
+    pub fn resolve(&self, rel_path: &str) -> Option<AssetPath<'static>> {
+        self.path.resolve_embed(rel_path).ok()
+    }
+
+    pub fn level(&self, identifier: &str) -> Option<&LdtkLevel> {
+        self.levels
+            .iter()
+            .find(|level| level.identifier == identifier)
+    }
+}
+
+#[derive(Debug)]
+pub struct LdtkTileset {
+    pub texture: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LdtkLevel {
+    pub identifier: String,
+    pub px_wid: i32,
+    pub px_hei: i32,
+    #[serde(rename = "fieldInstances", default)]
+    pub fields: LdtkFields,
+    /// layers ordered from top to bottom like in the editor
+    #[serde(default)]
+    pub layer_instances: Option<Vec<LdtkLayer>>,
+}
+
+impl LdtkLevel {
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.px_wid as f32, self.px_hei as f32)
+    }
+
+    /**
+     * The score needed on the previous level to unlock this one
+     */
+    pub fn unlock_score(&self) -> u32 {
+        self.fields.i32("unlockScore").unwrap_or(0).max(0) as u32
+    }
+
+    pub fn layers(&self) -> &[LdtkLayer] {
+        self.layer_instances.as_deref().unwrap_or_default()
+    }
+
+    /**
+     * Converts LDtk pixel coordinates (origin top left, y down)
+     * into world coordinates (origin in the center of the level, y up)
+     */
+    pub fn to_world(&self, px: Vec2) -> Vec2 {
+        Vec2::new(
+            px.x - self.px_wid as f32 / 2.0,
+            self.px_hei as f32 / 2.0 - px.y,
+        )
+    }
+
+    /**
+     * All entity instances of the level with the world position of their center
+     */
+    pub fn entities(&self) -> impl Iterator<Item = (Vec2, &LdtkEntity)> {
+        self.layers().iter().flat_map(move |layer| {
+            layer
+                .entity_instances
+                .iter()
+                .map(move |entity| (self.to_world(entity.center() + layer.offset()), entity))
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LdtkLayer {
+    #[serde(rename = "__identifier")]
+    pub identifier: String,
+    #[serde(rename = "__type")]
+    pub layer_type: String,
+    #[serde(rename = "__cWid")]
+    pub c_wid: i32,
+    #[serde(rename = "__cHei")]
+    pub c_hei: i32,
+    #[serde(rename = "__gridSize")]
+    pub grid_size: i32,
+    #[serde(rename = "__opacity")]
+    pub opacity: f32,
+    #[serde(rename = "__pxTotalOffsetX")]
+    pub px_total_offset_x: i32,
+    #[serde(rename = "__pxTotalOffsetY")]
+    pub px_total_offset_y: i32,
+    #[serde(rename = "__tilesetDefUid")]
+    pub tileset_def_uid: Option<i32>,
+    pub visible: bool,
+    #[serde(rename = "gridTiles", default)]
+    pub grid_tiles: Vec<LdtkTile>,
+    #[serde(rename = "autoLayerTiles", default)]
+    pub auto_layer_tiles: Vec<LdtkTile>,
+    #[serde(rename = "entityInstances", default)]
+    pub entity_instances: Vec<LdtkEntity>,
+    /// IntGrid values row by row, 0 is empty
+    #[serde(rename = "intGridCsv", default)]
+    pub int_grid_csv: Vec<i32>,
+}
+
+impl LdtkLayer {
+    pub fn tiles(&self) -> impl Iterator<Item = &LdtkTile> {
+        self.auto_layer_tiles.iter().chain(self.grid_tiles.iter())
+    }
+
+    pub fn offset(&self) -> Vec2 {
+        Vec2::new(self.px_total_offset_x as f32, self.px_total_offset_y as f32)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LdtkTile {
+    /// pixel position of the top left corner inside the layer
+    pub px: [i32; 2],
+    /// flip bits, 1 = x, 2 = y
+    pub f: u8,
+    /// tile id inside the tileset
+    pub t: i32,
+}
+
+impl LdtkTile {
+    pub fn flip_x(&self) -> bool {
+        self.f & 1 != 0
+    }
+
+    pub fn flip_y(&self) -> bool {
+        self.f & 2 != 0
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LdtkEntity {
+    #[serde(rename = "__identifier")]
+    pub identifier: String,
+    /// pixel position of the pivot inside the layer
+    pub px: [i32; 2],
+    #[serde(rename = "__pivot")]
+    pub pivot: [f32; 2],
+    pub width: i32,
+    pub height: i32,
+    #[serde(rename = "fieldInstances", default)]
+    pub fields: LdtkFields,
+}
+
+impl LdtkEntity {
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.width as f32, self.height as f32)
+    }
+
+    pub fn center(&self) -> Vec2 {
+        Vec2::new(self.px[0] as f32, self.px[1] as f32)
+            + (Vec2::splat(0.5) - Vec2::from_array(self.pivot)) * self.size()
+    }
+}
+
+/**
+ * The value of a custom field, typed by the field definition in LDtk
+ */
+#[derive(Deserialize, Debug)]
+pub struct LdtkField {
+    #[serde(rename = "__identifier")]
+    pub identifier: String,
+    #[serde(rename = "__value")]
+    pub value: serde_json::Value,
+}
+
+/**
+ * The custom fields of a level or an entity
+ */
+#[derive(Deserialize, Debug, Default)]
+#[serde(transparent)]
+pub struct LdtkFields(pub Vec<LdtkField>);
+
+impl LdtkFields {
+    pub fn get(&self, identifier: &str) -> Option<&serde_json::Value> {
+        self.0
+            .iter()
+            .find(|field| field.identifier == identifier)
+            .map(|field| &field.value)
+            .filter(|value| !value.is_null())
+    }
+
+    pub fn f32(&self, identifier: &str) -> Option<f32> {
+        self.get(identifier)
+            .and_then(|value| value.as_f64())
+            .map(|value| value as f32)
+    }
+
+    pub fn i32(&self, identifier: &str) -> Option<i32> {
+        self.get(identifier)
+            .and_then(|value| value.as_i64())
+            .map(|value| value as i32)
+    }
+
+    pub fn str(&self, identifier: &str) -> Option<&str> {
+        self.get(identifier).and_then(|value| value.as_str())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LdtkTilesetDef {
+    uid: i32,
+    rel_path: Option<String>,
+    #[serde(rename = "__cWid")]
+    c_wid: u32,
+    #[serde(rename = "__cHei")]
+    c_hei: u32,
+    tile_grid_size: u32,
+    spacing: u32,
+    padding: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct LdtkDefs {
+    tilesets: Vec<LdtkTilesetDef>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LdtkJson {
+    defs: LdtkDefs,
+    levels: Vec<LdtkLevel>,
+}
+
+#[derive(Default)]
+pub struct LdtkLoader;
+
+impl AssetLoader for LdtkLoader {
+    type Asset = LdtkProject;
+    type Settings = ();
+    type Error = io::Error;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<LdtkProject, io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let json: LdtkJson = serde_json::from_slice(&bytes)?;
+
+        let mut tilesets = HashMap::new();
+        for def in json.defs.tilesets {
+            // tilesets without an image are only used for enum icons
+            let rel_path = match def.rel_path {
+                Some(rel_path) => rel_path,
+                None => continue,
+            };
+            let path = load_context
+                .asset_path()
+                .resolve_embed(&rel_path)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let layout = TextureAtlasLayout::from_grid(
+                UVec2::splat(def.tile_grid_size),
+                def.c_wid,
+                def.c_hei,
+                Some(UVec2::splat(def.spacing)),
+                Some(UVec2::splat(def.padding)),
+            );
+
+            tilesets.insert(
+                def.uid,
+                LdtkTileset {
+                    texture: load_context.load(path),
+                    layout: load_context.add_labeled_asset(format!("tileset{}", def.uid), layout),
+                },
+            );
+        }
+
+        Ok(LdtkProject {
+            levels: json.levels,
+            tilesets,
+            path: load_context.asset_path().clone_owned(),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ldtk"]
+    }
+}
@@ -0,0 +1,58 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use bevy::app::PluginGroupBuilder;
+use bevy::prelude::*;
+use bevy_prng::WyRand;
+use bevy_rand::prelude::EntropyPlugin;
+
+pub mod cam;
+pub mod controls;
+pub mod death_screen;
+pub mod ext;
+pub mod gameplay;
+pub mod headless;
+pub mod ldtk;
+pub mod mainmenu;
+pub mod music;
+pub mod persistent;
+pub mod replay;
+pub mod seed;
+pub mod state;
+pub mod style;
+pub mod volume;
+
+/**
+ * The complete gameplay loop without window, renderer or audio device.
+ * Runs on top of `MinimalPlugins` as well as `DefaultPlugins`.
+ */
+pub struct MageanoidSimPlugins;
+
+impl PluginGroup for MageanoidSimPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(headless::HeadlessPlugin)
+            .add(EntropyPlugin::<WyRand>::default())
+            .add(seed::SeedPlugin)
+            .add(state::AppStatePlugin)
+            .add(persistent::PersistentPlugin)
+            .add(ldtk::LdtkPlugin)
+            .add(gameplay::GameplayPlugin)
+            .add(replay::ReplayPlugin)
+            .add(death_screen::DeathScreenPlugin)
+    }
+}
@@ -15,22 +15,23 @@
  * You should have received a copy of the GNU Affero General Public License
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
+use std::path::Path;
+
 use bevy::prelude::*;
-use bevy_prng::WyRand;
-use bevy_rand::prelude::EntropyPlugin;
 
-mod cam;
-mod controls;
-mod death_screen;
-mod ext;
-mod gameplay;
-mod ldtk;
-mod mainmenu;
-mod music;
-mod persistent;
-mod state;
-mod style;
-mod volume;
+use mageanoid::replay::{Replay, ReplayFilePlugin, ReplayPlayer};
+use mageanoid::seed::{RunSeed, SeedOverride};
+use mageanoid::{cam, controls, gameplay, mainmenu, music, style, volume, MageanoidSimPlugins};
+
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
+    }
+    None
+}
 
 fn main() {
     let mut app = App::new();
@@ -52,18 +53,33 @@ fn main() {
                 ..default()
             }),
     )
-    .add_plugins(EntropyPlugin::<WyRand>::default())
+    .add_plugins(MageanoidSimPlugins)
     .add_plugins(cam::CamPlugin)
     .add_plugins(controls::ControlPlugin)
-    .add_plugins(death_screen::DeathScreenPlugin)
-    .add_plugins(gameplay::GameplayPlugin)
-    .add_plugins(ldtk::LdtkPlugin)
+    .add_plugins(gameplay::collision::ColliderOverlayPlugin)
     .add_plugins(mainmenu::MainMenuPlugin)
     .add_plugins(music::MusicPlugin)
-    .add_plugins(persistent::PersistentPlugin)
-    .add_plugins(state::AppStatePlugin)
+    .add_plugins(ReplayFilePlugin)
     .add_plugins(style::StylePlugin)
     .add_plugins(volume::VolumePlugin);
 
+    if let Some(seed) = arg_value("--seed") {
+        match seed.parse::<RunSeed>() {
+            Ok(seed) => {
+                app.insert_resource(SeedOverride(Some(seed)));
+            }
+            Err(_) => error!("--seed expects a hexadecimal run seed"),
+        }
+    }
+
+    if let Some(path) = arg_value("--replay") {
+        match Replay::load(Path::new(&path)) {
+            Ok(replay) => {
+                app.insert_resource(ReplayPlayer::new(replay));
+            }
+            Err(err) => error!("failed to load replay {}: {}", path, err),
+        }
+    }
+
     app.run();
 }
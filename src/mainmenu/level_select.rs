@@ -0,0 +1,279 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use bevy::prelude::*;
+
+use crate::controls::ControlType;
+use crate::ldtk::project::{LdtkLevel, LdtkProject};
+use crate::ldtk::{LdtkProjectHandle, LevelBounds, LevelSelection};
+use crate::persistent::Score;
+use crate::state::AppState;
+use crate::style::*;
+
+use super::{MainMenu, MenuScreen};
+
+const PLAY_BUTTON: u8 = 3;
+const BACK_BUTTON: u8 = 4;
+const FIRST_LEVEL_BUTTON: u8 = 10;
+
+/**
+ * The first level is always open, every other level needs a high score on the one before it
+ */
+fn is_unlocked(levels: &[LdtkLevel], index: usize, score: &Score) -> bool {
+    index == 0
+        || score.level_high_score(&levels[index - 1].identifier) >= levels[index].unlock_score()
+}
+
+fn gamepad_icon(asset_server: &Res<AssetServer>, path: &'static str) -> impl Bundle {
+    (
+        ControlType::Gamepad,
+        ImageBundle {
+            image: asset_server.load(path).into(),
+            style: Style {
+                width: Val::VMin(6.4),
+                height: Val::VMin(6.4),
+                ..default()
+            },
+            ..default()
+        },
+    )
+}
+
+pub(super) fn spawn_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    project_handle: Res<LdtkProjectHandle>,
+    projects: Res<Assets<LdtkProject>>,
+    selection: Res<LevelSelection>,
+    #[cfg(feature = "storage")] score: Res<bevy_persistent::Persistent<Score>>,
+    #[cfg(not(feature = "storage"))] score: Res<Score>,
+) {
+    let container = NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        ..default()
+    };
+
+    let (play_btn, play_btn_text) = text_button("Play", PLAY_BUTTON);
+    let (back_btn, back_btn_text) = text_button("Back", BACK_BUTTON);
+
+    commands
+        .spawn((MainMenu, container))
+        .with_children(|parent| {
+            parent.spawn(text_title("Select Level"));
+            parent.spawn(v_space(5.0));
+
+            let levels = match projects.get(&project_handle.0) {
+                Some(project) => project.levels.as_slice(),
+                None => {
+                    parent.spawn(text("Loading levels..."));
+                    &[]
+                }
+            };
+
+            for (index, level) in levels.iter().enumerate() {
+                let selected = level.identifier == selection.0;
+                let label = if selected {
+                    format!("> {} <", level.identifier)
+                } else {
+                    level.identifier.clone()
+                };
+                let info = if is_unlocked(levels, index, &score) {
+                    format!("High Score: {}", score.level_high_score(&level.identifier))
+                } else {
+                    format!(
+                        "Score {} on {} to unlock",
+                        level.unlock_score(),
+                        levels[index - 1].identifier
+                    )
+                };
+                let (level_btn, level_btn_text) =
+                    text_button(label, FIRST_LEVEL_BUTTON + index as u8);
+
+                parent.spawn(hbox()).with_children(|parent| {
+                    parent.spawn(level_btn).with_children(|parent| {
+                        parent.spawn(level_btn_text);
+                    });
+                    parent.spawn(h_space(1.0));
+                    parent.spawn(text(info));
+                });
+                parent.spawn(v_space(1.0));
+            }
+
+            parent.spawn(hbox()).with_children(|parent| {
+                parent.spawn(gamepad_icon(&asset_server, "ui/dpad_left.png"));
+                parent.spawn(gamepad_icon(&asset_server, "ui/dpad_right.png"));
+                parent.spawn(h_space(1.0));
+                parent.spawn((ControlType::Gamepad, text("Select")));
+            });
+
+            parent.spawn(v_space(5.0));
+
+            parent.spawn(hbox()).with_children(|parent| {
+                parent.spawn(play_btn).with_children(|parent| {
+                    parent.spawn(play_btn_text);
+                });
+                parent.spawn(gamepad_icon(&asset_server, "ui/face_north.png"));
+            });
+
+            parent.spawn(v_space(5.0));
+
+            parent.spawn(hbox()).with_children(|parent| {
+                parent.spawn(back_btn).with_children(|parent| {
+                    parent.spawn(back_btn_text);
+                });
+                parent.spawn(gamepad_icon(&asset_server, "ui/face_south.png"));
+            });
+        });
+}
+
+/**
+ * Starts the selected level once it is spawned behind the menu
+ */
+fn play(
+    levels: &[LdtkLevel],
+    selection: &LevelSelection,
+    score: &Score,
+    level_spawned: bool,
+    next_state: &mut NextState<AppState>,
+) {
+    let unlocked = levels
+        .iter()
+        .position(|level| level.identifier == selection.0)
+        .is_some_and(|index| is_unlocked(levels, index, score));
+    if unlocked && level_spawned {
+        next_state.set(AppState::InGame);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn on_button_click(
+    mut next_state: ResMut<NextState<AppState>>,
+    mut screen: ResMut<MenuScreen>,
+    mut selection: ResMut<LevelSelection>,
+    project_handle: Res<LdtkProjectHandle>,
+    projects: Res<Assets<LdtkProject>>,
+    bounds: Option<Res<LevelBounds>>,
+    #[cfg(feature = "storage")] score: Res<bevy_persistent::Persistent<Score>>,
+    #[cfg(not(feature = "storage"))] score: Res<Score>,
+    button_q: Query<(&Interaction, &ButtonId), Changed<Interaction>>,
+) {
+    let levels = match projects.get(&project_handle.0) {
+        Some(project) => project.levels.as_slice(),
+        None => &[],
+    };
+
+    for (interaction, button_id) in button_q.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button_id.id {
+            PLAY_BUTTON => play(
+                levels,
+                &selection,
+                &score,
+                bounds.is_some(),
+                &mut next_state,
+            ),
+            BACK_BUTTON => *screen = MenuScreen::Title,
+            id if id >= FIRST_LEVEL_BUTTON => {
+                let index = (id - FIRST_LEVEL_BUTTON) as usize;
+                if index < levels.len()
+                    && is_unlocked(levels, index, &score)
+                    && selection.0 != levels[index].identifier
+                {
+                    selection.0 = levels[index].identifier.clone();
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn gamepad_input(
+    mut next_state: ResMut<NextState<AppState>>,
+    mut screen: ResMut<MenuScreen>,
+    mut selection: ResMut<LevelSelection>,
+    project_handle: Res<LdtkProjectHandle>,
+    projects: Res<Assets<LdtkProject>>,
+    bounds: Option<Res<LevelBounds>>,
+    #[cfg(feature = "storage")] score: Res<bevy_persistent::Persistent<Score>>,
+    #[cfg(not(feature = "storage"))] score: Res<Score>,
+    gamepad: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+) {
+    let levels = match projects.get(&project_handle.0) {
+        Some(project) => project.levels.as_slice(),
+        None => &[],
+    };
+
+    for gamepad in gamepad.iter() {
+        let pressed = |button_type| {
+            buttons.just_pressed(GamepadButton {
+                gamepad,
+                button_type,
+            })
+        };
+
+        if pressed(GamepadButtonType::South) || pressed(GamepadButtonType::East) {
+            *screen = MenuScreen::Title;
+            return;
+        }
+
+        if pressed(GamepadButtonType::North) || pressed(GamepadButtonType::West) {
+            play(
+                levels,
+                &selection,
+                &score,
+                bounds.is_some(),
+                &mut next_state,
+            );
+            return;
+        }
+
+        let step = if pressed(GamepadButtonType::DPadRight) {
+            1
+        } else if pressed(GamepadButtonType::DPadLeft) {
+            levels.len().saturating_sub(1)
+        } else {
+            continue;
+        };
+        let current = levels
+            .iter()
+            .position(|level| level.identifier == selection.0)
+            .unwrap_or(0);
+        // wrap around and skip locked levels
+        let next = (1..=levels.len())
+            .map(|offset| (current + offset * step) % levels.len())
+            .find(|index| is_unlocked(levels, *index, &score));
+        if let Some(next) = next {
+            if selection.0 != levels[next].identifier {
+                selection.0 = levels[next].identifier.clone();
+            }
+        }
+        return;
+    }
+}
@@ -21,19 +21,31 @@ extern crate bevy;
 use bevy::prelude::*;
 
 use crate::controls::ControlType;
+use crate::ldtk::{LevelBounds, LevelSelection};
 use crate::persistent::Mixer;
+use crate::replay::{last_replay_path, Replay, ReplayPlayer};
 use crate::state::AppState;
 use crate::style::*;
 use crate::volume::volume_control_ui;
 
+mod level_select;
+
 pub struct MainMenuPlugin;
 
 #[derive(Component, Debug)]
 struct MainMenu;
 
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum MenuScreen {
+    #[default]
+    Title,
+    LevelSelect,
+}
+
 fn spawn_ui(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    selection: Res<LevelSelection>,
     #[cfg(feature = "storage")] score: Res<bevy_persistent::Persistent<crate::persistent::Score>>,
     #[cfg(not(feature = "storage"))] score: Res<crate::persistent::Score>,
     #[cfg(not(feature = "storage"))] mixer: Res<Mixer>,
@@ -54,6 +66,7 @@ fn spawn_ui(
 
     let (start_btn, start_btn_text) = text_button("Start", 0);
     let (exit_btn, exit_btn_text) = text_button("Exit", 1);
+    let (replay_btn, replay_btn_text) = text_button("Replay", 2);
 
     commands
         .spawn((
@@ -88,7 +101,10 @@ fn spawn_ui(
         .spawn((MainMenu, container))
         .with_children(|parent| {
             parent.spawn(text_title("Mageanoid"));
-            parent.spawn(text(format!("High Score: {}", score.high_score)));
+            parent.spawn(text(format!(
+                "High Score: {}",
+                score.level_high_score(&selection.0)
+            )));
             parent.spawn(v_space(20.0));
 
             parent
@@ -123,6 +139,14 @@ fn spawn_ui(
 
             parent.spawn(v_space(5.0));
 
+            if last_replay_path().is_some_and(|path| path.exists()) {
+                parent.spawn(replay_btn).with_children(|parent| {
+                    parent.spawn(replay_btn_text);
+                });
+
+                parent.spawn(v_space(5.0));
+            }
+
             parent
                 .spawn(NodeBundle {
                     style: Style {
@@ -218,17 +242,28 @@ fn spawn_ui(
 }
 
 fn on_button_click(
-    mut next_state: ResMut<NextState<AppState>>,
+    mut commands: Commands,
+    mut screen: ResMut<MenuScreen>,
     mut app_exit_events: ResMut<Events<AppExit>>,
     button_q: Query<(&Interaction, &ButtonId), (Changed<Interaction>, With<Button>)>,
 ) {
     for (interaction, button_id) in button_q.iter() {
         if *interaction == Interaction::Pressed {
             match button_id.id {
-                0 => next_state.set(AppState::InGame),
+                0 => *screen = MenuScreen::LevelSelect,
                 1 => {
                     app_exit_events.send(AppExit::Success);
                 }
+                2 => {
+                    let path = match last_replay_path() {
+                        Some(path) => path,
+                        None => return,
+                    };
+                    match Replay::load(&path) {
+                        Ok(replay) => commands.insert_resource(ReplayPlayer::new(replay)),
+                        Err(err) => warn!("failed to load replay: {}", err),
+                    }
+                }
                 _ => (),
             }
         }
@@ -236,7 +271,7 @@ fn on_button_click(
 }
 
 fn gamepad_input(
-    mut next_state: ResMut<NextState<AppState>>,
+    mut screen: ResMut<MenuScreen>,
     gamepad: Res<Gamepads>,
     buttons: Res<ButtonInput<GamepadButton>>,
     mut app_exit_events: ResMut<Events<AppExit>>,
@@ -266,12 +301,16 @@ fn gamepad_input(
                 button_type: GamepadButtonType::North,
             },
         ]) {
-            next_state.set(AppState::InGame);
+            *screen = MenuScreen::LevelSelect;
             return;
         }
     }
 }
 
+fn show_title(mut screen: ResMut<MenuScreen>) {
+    *screen = MenuScreen::Title;
+}
+
 fn despawn_ui(mut commands: Commands, query: Query<Entity, With<MainMenu>>) {
     for entity in query.iter() {
         commands.entity(entity).despawn_recursive();
@@ -280,9 +319,35 @@ fn despawn_ui(mut commands: Commands, query: Query<Entity, With<MainMenu>>) {
 
 impl Plugin for MainMenuPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(AppState::MainMenu), spawn_ui)
-            .add_systems(Update, on_button_click.run_if(in_state(AppState::MainMenu)))
-            .add_systems(Update, gamepad_input.run_if(in_state(AppState::MainMenu)))
+        app.init_resource::<MenuScreen>()
+            .add_systems(OnEnter(AppState::MainMenu), show_title)
+            .add_systems(
+                Update,
+                (
+                    despawn_ui,
+                    spawn_ui.run_if(resource_equals(MenuScreen::Title)),
+                    level_select::spawn_ui.run_if(resource_equals(MenuScreen::LevelSelect)),
+                )
+                    .chain()
+                    .run_if(
+                        in_state(AppState::MainMenu).and_then(
+                            resource_changed::<MenuScreen>
+                                .or_else(resource_changed::<LevelSelection>)
+                                .or_else(resource_added::<LevelBounds>),
+                        ),
+                    ),
+            )
+            .add_systems(
+                Update,
+                (
+                    (on_button_click, gamepad_input).run_if(resource_equals(MenuScreen::Title)),
+                    (level_select::on_button_click, level_select::gamepad_input)
+                        .run_if(resource_equals(MenuScreen::LevelSelect)),
+                )
+                    .run_if(
+                        in_state(AppState::MainMenu).and_then(not(resource_changed::<MenuScreen>)),
+                    ),
+            )
             .add_systems(OnExit(AppState::MainMenu), despawn_ui);
     }
 }
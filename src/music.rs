@@ -18,8 +18,15 @@
 use bevy::audio::{PlaybackMode, Volume};
 use bevy::prelude::*;
 
+use crate::gameplay::boss::BossDefeated;
+use crate::persistent::Mixer;
 use crate::volume::AdjustingVolumeAudio;
 
+const BOSS_STING: [&str; 2] = [
+    "sounds/18_Thunder_02.wav",
+    "sounds/04_Fire_explosion_04_medium.wav",
+];
+
 fn spawn_music(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.spawn((
         AudioBundle {
@@ -34,10 +41,33 @@ fn spawn_music(mut commands: Commands, asset_server: Res<AssetServer>) {
     ));
 }
 
+fn boss_sting(
+    mut commands: Commands,
+    mut evr_boss_defeated: EventReader<BossDefeated>,
+    asset_server: Res<AssetServer>,
+    #[cfg(not(feature = "storage"))] mixer: Res<Mixer>,
+    #[cfg(feature = "storage")] mixer: Res<bevy_persistent::Persistent<Mixer>>,
+) {
+    if evr_boss_defeated.read().count() == 0 {
+        return;
+    }
+    for path in BOSS_STING {
+        commands.spawn(AudioBundle {
+            source: asset_server.load(path),
+            settings: PlaybackSettings {
+                mode: PlaybackMode::Despawn,
+                volume: mixer.as_volume(),
+                ..default()
+            },
+        });
+    }
+}
+
 pub struct MusicPlugin;
 
 impl Plugin for MusicPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(PostStartup, spawn_music);
+        app.add_systems(PostStartup, spawn_music)
+            .add_systems(Update, boss_sting);
     }
 }
@@ -15,26 +15,53 @@
  * You should have received a copy of the GNU Affero General Public License
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
+use std::collections::HashMap;
+
 use bevy::audio::Volume;
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use crate::ldtk::LevelSelection;
 use crate::state::*;
 
 #[derive(Resource, Serialize, Deserialize, Default, Debug)]
 pub struct Score {
     pub current_score: u32,
-    pub high_score: u32,
+    /// the level the current score is played on
+    pub level: String,
+    /// high score per level identifier
+    pub high_scores: HashMap<String, u32>,
+}
+
+/**
+ * The score file layout from before high scores were kept per level
+ */
+#[cfg(feature = "storage")]
+#[derive(Deserialize, Debug)]
+struct LegacyScore {
+    #[allow(dead_code)]
+    current_score: u32,
+    high_score: u32,
 }
 
 impl Score {
-    pub fn reset(&mut self) {
+    pub fn reset(&mut self, level: &str) {
         self.current_score = 0;
+        self.level = level.to_string();
     }
 
     pub fn increase(&mut self, amount: u32) {
         self.current_score += amount;
-        self.high_score = self.high_score.max(self.current_score);
+        let high_score = self.high_scores.entry(self.level.clone()).or_default();
+        *high_score = (*high_score).max(self.current_score);
+    }
+
+    pub fn high_score(&self) -> u32 {
+        self.level_high_score(&self.level)
+    }
+
+    pub fn level_high_score(&self, level: &str) -> u32 {
+        self.high_scores.get(level).copied().unwrap_or(0)
     }
 }
 
@@ -60,13 +87,49 @@ impl Mixer {
 }
 
 #[cfg(feature = "storage")]
-fn reset_score(mut score: ResMut<bevy_persistent::Persistent<Score>>) {
-    score.reset();
+fn reset_score(
+    mut score: ResMut<bevy_persistent::Persistent<Score>>,
+    selection: Res<LevelSelection>,
+) {
+    score.reset(&selection.0);
 }
 
 #[cfg(not(feature = "storage"))]
-fn reset_score(mut score: ResMut<Score>) {
-    score.reset();
+fn reset_score(mut score: ResMut<Score>, selection: Res<LevelSelection>) {
+    score.reset(&selection.0);
+}
+
+/**
+ * Rewrites a score file of the old layout so its high score counts for the first level,
+ * the persistent resource would throw it away otherwise
+ */
+#[cfg(feature = "storage")]
+fn migrate_score(path: &std::path::Path) {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(_) => return,
+    };
+    if bincode::deserialize::<Score>(&bytes).is_ok() {
+        return;
+    }
+    let legacy = match bincode::deserialize::<LegacyScore>(&bytes) {
+        Ok(legacy) => legacy,
+        Err(_) => return,
+    };
+    let level = LevelSelection::default().0;
+    let score = Score {
+        current_score: 0,
+        high_scores: HashMap::from([(level.clone(), legacy.high_score)]),
+        level,
+    };
+    match bincode::serialize(&score) {
+        Ok(bytes) => {
+            if let Err(err) = std::fs::write(path, bytes) {
+                warn!("failed to migrate score: {}", err);
+            }
+        }
+        Err(err) => warn!("failed to migrate score: {}", err),
+    }
 }
 
 #[cfg(feature = "storage")]
@@ -82,10 +145,12 @@ impl Plugin for PersistentPlugin {
         #[cfg(feature = "storage")]
         {
             if let Some(config_dir) = dirs::config_dir() {
+                let score_path = config_dir.join("mageanoid").join("score");
+                migrate_score(&score_path);
                 let score_resource = bevy_persistent::Persistent::<Score>::builder()
                     .name("score")
                     .format(bevy_persistent::StorageFormat::Bincode)
-                    .path(config_dir.join("mageanoid").join("score"))
+                    .path(score_path)
                     .default(Score::default())
                     .revertible(true)
                     .revert_to_default_on_deserialization_errors(true)
@@ -0,0 +1,444 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::gameplay::input::PlayerInput;
+use crate::gameplay::GameplaySet;
+use crate::ldtk::{LevelBounds, LevelSelection};
+use crate::persistent::Score;
+use crate::seed::{choose_seed, RunSeed};
+use crate::state::{AppState, ON_ENTER_GAMEPLAY, ON_EXIT_GAMEPLAY};
+
+const REPLAY_VERSION: u32 = 6;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+struct RecordedInput {
+    movement: [f32; 2],
+    sprint: bool,
+    dodge: bool,
+    fire: Option<[f32; 2]>,
+    spell: usize,
+    upgrade: Option<usize>,
+}
+
+impl From<PlayerInput> for RecordedInput {
+    fn from(input: PlayerInput) -> Self {
+        Self {
+            movement: input.movement.to_array(),
+            sprint: input.sprint,
+            dodge: input.dodge,
+            fire: input.fire.map(|fire| fire.to_array()),
+            spell: input.spell,
+            upgrade: input.upgrade,
+        }
+    }
+}
+
+impl From<RecordedInput> for PlayerInput {
+    fn from(input: RecordedInput) -> Self {
+        Self {
+            movement: Vec2::from_array(input.movement),
+            sprint: input.sprint,
+            dodge: input.dodge,
+            fire: input.fire.map(Vec2::from_array),
+            spell: input.spell,
+            upgrade: input.upgrade,
+        }
+    }
+}
+
+/**
+ * The player input of every fixed tick of one run
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    version: u32,
+    pub seed: RunSeed,
+    /// identifier of the LDtk level the run was played on
+    pub level: String,
+    pub score: u32,
+    /// fixed timestep in nanoseconds
+    timestep: u64,
+    /// run length encoded inputs as (tick count, input)
+    inputs: Vec<(u32, RecordedInput)>,
+}
+
+impl Replay {
+    pub fn new(seed: RunSeed, level: String, timestep: Duration) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            level,
+            score: 0,
+            timestep: timestep.as_nanos() as u64,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn timestep(&self) -> Duration {
+        Duration::from_nanos(self.timestep)
+    }
+
+    pub fn tick_count(&self) -> usize {
+        self.inputs.iter().map(|(count, _)| *count as usize).sum()
+    }
+
+    fn push(&mut self, input: PlayerInput) {
+        let input = RecordedInput::from(input);
+        match self.inputs.last_mut() {
+            Some((count, last)) if *last == input => *count += 1,
+            _ => self.inputs.push((1, input)),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let replay: Replay = bincode::deserialize(&bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if replay.version != REPLAY_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported replay version {}", replay.version),
+            ));
+        }
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let bytes = bincode::serialize(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, bytes)
+    }
+}
+
+/**
+ * Where the replay of the last run is stored
+ */
+pub fn last_replay_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|data_dir| data_dir.join("mageanoid").join("last.replay"))
+}
+
+#[derive(Resource, Debug)]
+struct ReplayRecorder(Replay);
+
+/**
+ * The recording of the last run that was played live
+ */
+#[derive(Resource, Debug, Default)]
+pub struct LastReplay(pub Option<Replay>);
+
+/**
+ * Feeds or records the input of a tick, systems using the input run after it
+ */
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReplayInputSet;
+
+/**
+ * Feeds a recorded run back into the game instead of the live input
+ */
+#[derive(Resource, Debug)]
+pub struct ReplayPlayer {
+    replay: Replay,
+    inputs: Vec<PlayerInput>,
+    tick: usize,
+    /// the timestep in effect before the playback, restored when it ends
+    previous_timestep: Option<Duration>,
+    /// the level selected before the playback, restored when it ends
+    previous_level: Option<String>,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        let inputs = replay
+            .inputs
+            .iter()
+            .flat_map(|(count, input)| {
+                std::iter::repeat_n(PlayerInput::from(*input), *count as usize)
+            })
+            .collect();
+
+        Self {
+            replay,
+            inputs,
+            tick: 0,
+            previous_timestep: None,
+            previous_level: None,
+        }
+    }
+
+    pub fn seed(&self) -> RunSeed {
+        self.replay.seed
+    }
+}
+
+fn start_recording(
+    mut commands: Commands,
+    seed: Res<RunSeed>,
+    selection: Res<LevelSelection>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    commands.insert_resource(ReplayRecorder(Replay::new(
+        *seed,
+        selection.0.clone(),
+        fixed_time.timestep(),
+    )));
+}
+
+fn record_input(mut recorder: ResMut<ReplayRecorder>, input: Res<PlayerInput>) {
+    recorder.0.push(*input);
+}
+
+fn finish_recording(
+    mut commands: Commands,
+    recorder: Option<ResMut<ReplayRecorder>>,
+    mut last_replay: ResMut<LastReplay>,
+    #[cfg(feature = "storage")] score: Res<bevy_persistent::Persistent<Score>>,
+    #[cfg(not(feature = "storage"))] score: Res<Score>,
+) {
+    let mut recorder = match recorder {
+        Some(recorder) => recorder,
+        None => return,
+    };
+    recorder.0.score = score.current_score;
+
+    last_replay.0 = Some(recorder.0.clone());
+    commands.remove_resource::<ReplayRecorder>();
+}
+
+fn save_last_replay(last_replay: Res<LastReplay>) {
+    let replay = match &last_replay.0 {
+        Some(replay) => replay,
+        None => return,
+    };
+
+    if let Some(path) = last_replay_path() {
+        match replay.save(&path) {
+            Ok(()) => info!("saved replay to {}", path.display()),
+            Err(err) => warn!("failed to save replay: {}", err),
+        }
+    }
+}
+
+fn begin_playback(
+    mut player: ResMut<ReplayPlayer>,
+    mut selection: ResMut<LevelSelection>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    // swap the level first, this runs again once it is spawned
+    if selection.0 != player.replay.level {
+        if player.previous_level.is_none() {
+            player.previous_level = Some(selection.0.clone());
+        }
+        selection.0 = player.replay.level.clone();
+        return;
+    }
+
+    info!(
+        "playing replay with seed {} ({} ticks)",
+        player.seed(),
+        player.replay.tick_count()
+    );
+
+    if player.previous_timestep.is_none() {
+        player.previous_timestep = Some(fixed_time.timestep());
+    }
+    fixed_time.set_timestep(player.replay.timestep());
+    next_state.set(AppState::InGame);
+}
+
+fn feed_input(
+    mut player: ResMut<ReplayPlayer>,
+    mut input: ResMut<PlayerInput>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    match player.inputs.get(player.tick) {
+        Some(recorded) => *input = *recorded,
+        None => {
+            *input = PlayerInput::default();
+            next_state.set(AppState::MainMenu);
+            return;
+        }
+    }
+    player.tick += 1;
+
+    // the gameplay sets check for a pending transition once per tick,
+    // ending with the last recorded tick keeps the playback from running an extra one
+    if player.tick == player.inputs.len() {
+        next_state.set(AppState::MainMenu);
+    }
+}
+
+fn end_playback(
+    mut commands: Commands,
+    player: Option<Res<ReplayPlayer>>,
+    mut selection: ResMut<LevelSelection>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    #[cfg(feature = "storage")] score: Res<bevy_persistent::Persistent<Score>>,
+    #[cfg(not(feature = "storage"))] score: Res<Score>,
+) {
+    let player = match player {
+        Some(player) => player,
+        None => return,
+    };
+
+    if score.current_score == player.replay.score {
+        info!("replay finished with score {}", score.current_score);
+    } else {
+        warn!(
+            "replay diverged: recorded score {}, replayed score {}",
+            player.replay.score, score.current_score
+        );
+    }
+
+    if let Some(timestep) = player.previous_timestep {
+        fixed_time.set_timestep(timestep);
+    }
+    if let Some(level) = &player.previous_level {
+        selection.0 = level.clone();
+    }
+    commands.remove_resource::<ReplayPlayer>();
+}
+
+/**
+ * Records every live run into `LastReplay` and plays back a `ReplayPlayer`
+ */
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LastReplay>()
+            .add_systems(
+                ON_ENTER_GAMEPLAY,
+                start_recording
+                    .after(choose_seed)
+                    .run_if(not(resource_exists::<ReplayPlayer>)),
+            )
+            .add_systems(
+                Update,
+                begin_playback.run_if(
+                    resource_added::<ReplayPlayer>
+                        .or_else(resource_added::<LevelBounds>)
+                        .and_then(resource_exists::<ReplayPlayer>)
+                        .and_then(in_state(AppState::MainMenu))
+                        .and_then(resource_exists::<LevelBounds>),
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    feed_input.run_if(resource_exists::<ReplayPlayer>),
+                    record_input.run_if(resource_exists::<ReplayRecorder>),
+                )
+                    .chain()
+                    .in_set(ReplayInputSet)
+                    .in_set(GameplaySet::Input),
+            )
+            .add_systems(ON_EXIT_GAMEPLAY, (finish_recording, end_playback));
+    }
+}
+
+/**
+ * Writes the last replay to `last_replay_path`, left out by tools and tests
+ * so they don't overwrite the recording of the player
+ */
+pub struct ReplayFilePlugin;
+
+impl Plugin for ReplayFilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            ON_EXIT_GAMEPLAY,
+            save_last_replay
+                .after(finish_recording)
+                .run_if(resource_changed::<LastReplay>),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(x: f32, fire: bool) -> PlayerInput {
+        PlayerInput {
+            movement: Vec2::new(x, 0.0),
+            fire: fire.then_some(Vec2::X),
+            ..default()
+        }
+    }
+
+    fn replay(inputs: &[PlayerInput]) -> Replay {
+        let mut replay = Replay::new(RunSeed(7), "Level_0".into(), Duration::from_millis(16));
+        for input in inputs {
+            replay.push(*input);
+        }
+        replay
+    }
+
+    #[test]
+    fn push_merges_equal_ticks() {
+        let still = input(0.0, false);
+        let walk = input(1.0, false);
+        let cast = input(1.0, true);
+        let replay = replay(&[still, still, still, walk, cast, walk, walk]);
+
+        let runs: Vec<(u32, PlayerInput)> = replay
+            .inputs
+            .iter()
+            .map(|(count, input)| (*count, PlayerInput::from(*input)))
+            .collect();
+        assert_eq!(runs, vec![(3, still), (1, walk), (1, cast), (2, walk)]);
+        assert_eq!(replay.tick_count(), 7);
+    }
+
+    #[test]
+    fn player_expands_every_tick() {
+        let inputs = [
+            input(0.0, false),
+            input(0.0, false),
+            input(-1.0, true),
+            input(0.5, false),
+            input(0.5, false),
+        ];
+        let player = ReplayPlayer::new(replay(&inputs));
+        assert_eq!(player.inputs, inputs);
+    }
+
+    #[test]
+    fn survives_encoding() {
+        let inputs = [input(0.25, true), input(0.25, true), input(0.0, false)];
+        let replay = replay(&inputs);
+        let bytes = bincode::serialize(&replay).unwrap();
+        let decoded: Replay = bincode::deserialize(&bytes).unwrap();
+
+        assert_eq!(decoded.seed, replay.seed);
+        assert_eq!(decoded.level, replay.level);
+        assert_eq!(decoded.timestep(), replay.timestep());
+        assert_eq!(decoded.inputs, replay.inputs);
+    }
+}
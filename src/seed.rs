@@ -0,0 +1,105 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::fmt;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use bevy_prng::WyRand;
+use bevy_rand::prelude::GlobalEntropy;
+use rand_core::{OsRng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::replay::ReplayPlayer;
+use crate::state::ON_ENTER_GAMEPLAY;
+
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RunSeed(pub u64);
+
+impl fmt::Display for RunSeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016X}", self.0)
+    }
+}
+
+impl FromStr for RunSeed {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s.trim(), 16).map(RunSeed)
+    }
+}
+
+/**
+ * Seed supplied by the user, used for every run instead of a random one
+ */
+#[derive(Resource, Debug, Default)]
+pub struct SeedOverride(pub Option<RunSeed>);
+
+/**
+ * One random stream per system, so adding randomness to one system
+ * does not change the outcome of the others
+ */
+#[derive(Resource, Debug)]
+pub struct RunRng {
+    pub enemy_spawn: WyRand,
+    pub enemy_target: WyRand,
+    pub upgrades: WyRand,
+    pub pickups: WyRand,
+}
+
+impl RunRng {
+    fn new(entropy: &mut impl RngCore) -> Self {
+        Self {
+            enemy_spawn: WyRand::seed_from_u64(entropy.next_u64()),
+            enemy_target: WyRand::seed_from_u64(entropy.next_u64()),
+            upgrades: WyRand::seed_from_u64(entropy.next_u64()),
+            pickups: WyRand::seed_from_u64(entropy.next_u64()),
+        }
+    }
+}
+
+pub fn choose_seed(
+    mut commands: Commands,
+    mut entropy: ResMut<GlobalEntropy<WyRand>>,
+    seed_override: Res<SeedOverride>,
+    replay: Option<Res<ReplayPlayer>>,
+) {
+    let seed = match (replay, seed_override.0) {
+        (Some(replay), _) => replay.seed(),
+        (None, Some(seed)) => seed,
+        // never from the global entropy, it is reseeded with every run seed
+        (None, None) => RunSeed(OsRng.next_u64()),
+    };
+    info!("run seed {}", seed);
+
+    entropy.reseed(seed.0.to_le_bytes());
+    commands.insert_resource(RunRng::new(&mut *entropy));
+    commands.insert_resource(seed);
+}
+
+pub struct SeedPlugin;
+
+impl Plugin for SeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RunSeed::default())
+            .init_resource::<SeedOverride>()
+            .insert_resource(RunRng::new(&mut WyRand::seed_from_u64(0)))
+            .add_systems(ON_ENTER_GAMEPLAY, choose_seed);
+    }
+}
@@ -0,0 +1,74 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+
+use mageanoid::gameplay::archetype::{EnemyArchetypes, EnemyArchetypesHandle};
+use mageanoid::ldtk::LevelBounds;
+use mageanoid::seed::{RunSeed, SeedOverride};
+use mageanoid::state::AppState;
+use mageanoid::MageanoidSimPlugins;
+
+const LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/**
+ * The simulation on top of `MinimalPlugins`, running exactly one fixed tick per update
+ */
+pub fn sim_app(seed: RunSeed) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugins(MageanoidSimPlugins)
+        .insert_resource(SeedOverride(Some(seed)));
+    let timestep = app.world().resource::<Time<Fixed>>().timestep();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep));
+
+    let started = Instant::now();
+    while !loaded(&app) {
+        assert!(started.elapsed() < LOAD_TIMEOUT, "assets did not load");
+        app.update();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    app
+}
+
+fn loaded(app: &App) -> bool {
+    let world = app.world();
+    let archetypes_loaded = world
+        .get_resource::<EnemyArchetypesHandle>()
+        .map(|handle| {
+            world
+                .resource::<Assets<EnemyArchetypes>>()
+                .contains(&handle.0)
+        })
+        .unwrap_or(false);
+    archetypes_loaded && world.contains_resource::<LevelBounds>()
+}
+
+pub fn state(app: &App) -> AppState {
+    app.world().resource::<State<AppState>>().get().clone()
+}
+
+pub fn set_state(app: &mut App, state: AppState) {
+    app.world_mut()
+        .resource_mut::<NextState<AppState>>()
+        .set(state);
+    app.update();
+}
@@ -0,0 +1,118 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+mod common;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+
+use mageanoid::gameplay::input::PlayerInput;
+use mageanoid::gameplay::player::Player;
+use mageanoid::gameplay::GameplaySet;
+use mageanoid::persistent::Score;
+use mageanoid::replay::{LastReplay, ReplayPlayer};
+use mageanoid::seed::RunSeed;
+use mageanoid::state::AppState;
+
+const TICKS: usize = 2400;
+/// upper bound for the playback
+const PLAYBACK_UPDATES: usize = TICKS + 100;
+
+/**
+ * Player position after every simulated tick
+ */
+#[derive(Resource, Debug, Default)]
+struct Trace(Vec<Vec3>);
+
+fn trace_player(player_q: Query<&Transform, With<Player>>, mut trace: ResMut<Trace>) {
+    if let Ok(transform) = player_q.get_single() {
+        trace.0.push(transform.translation);
+    }
+}
+
+fn traced_app(seed: RunSeed) -> App {
+    let mut app = common::sim_app(seed);
+    app.init_resource::<Trace>().add_systems(
+        FixedUpdate,
+        trace_player.in_set(GameplaySet::Combat),
+    );
+    app
+}
+
+/**
+ * Walks in a square and keeps casting, through the devices like a live player
+ */
+fn press_keys(app: &mut App, tick: usize) {
+    const KEYS: [KeyCode; 4] = [KeyCode::KeyD, KeyCode::KeyW, KeyCode::KeyA, KeyCode::KeyS];
+    let mut keys = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+    keys.release_all();
+    keys.press(KEYS[tick / 90 % KEYS.len()]);
+    if tick % 120 < 30 {
+        keys.press(KeyCode::ShiftLeft);
+    }
+
+    let mut input = app.world_mut().resource_mut::<PlayerInput>();
+    if tick.is_multiple_of(8) {
+        input.fire = Some(Vec2::from_angle(tick as f32 * 0.37));
+    }
+    input.upgrade = Some(tick % 3);
+}
+
+#[test]
+fn playback_matches_the_recording() {
+    let mut app = traced_app(RunSeed(0x5EED));
+    common::set_state(&mut app, AppState::InGame);
+    for tick in 0..TICKS {
+        press_keys(&mut app, tick);
+        app.update();
+    }
+    assert_eq!(common::state(&app), AppState::InGame, "the player died");
+    common::set_state(&mut app, AppState::MainMenu);
+
+    let recorded_score = app.world().resource::<Score>().current_score;
+    let recorded_trace = std::mem::take(&mut app.world_mut().resource_mut::<Trace>().0);
+    let replay = app
+        .world()
+        .resource::<LastReplay>()
+        .0
+        .clone()
+        .expect("the run was not recorded");
+    assert!(recorded_score > 0, "nothing was killed to compare");
+
+    // another seed, the replay has to bring its own, and another frame rate
+    let mut app = traced_app(RunSeed(1));
+    let timestep = app.world().resource::<Time<Fixed>>().timestep();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep * 2));
+    app.insert_resource(ReplayPlayer::new(replay));
+    let mut started = false;
+    for _ in 0..PLAYBACK_UPDATES {
+        app.update();
+        started |= common::state(&app) == AppState::InGame;
+        if started && common::state(&app) == AppState::MainMenu {
+            break;
+        }
+    }
+    assert!(started, "the playback did not start");
+    assert_eq!(common::state(&app), AppState::MainMenu, "the playback did not end");
+
+    let replayed_score = app.world().resource::<Score>().current_score;
+    let replayed_trace = &app.world().resource::<Trace>().0;
+    assert_eq!(replayed_trace.len(), recorded_trace.len());
+    assert_eq!(*replayed_trace, recorded_trace);
+    assert_eq!(replayed_score, recorded_score);
+}
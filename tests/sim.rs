@@ -0,0 +1,76 @@
+/*
+ * Mageanoid - A computer game
+ * Copyright (C) 2024  Frank Mayer
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+mod common;
+
+use bevy::prelude::*;
+
+use mageanoid::gameplay::enemy::Enemy;
+use mageanoid::gameplay::input::PlayerInput;
+use mageanoid::gameplay::player::Player;
+use mageanoid::persistent::Score;
+use mageanoid::replay::LastReplay;
+use mageanoid::seed::RunSeed;
+use mageanoid::state::AppState;
+
+/// long enough for the first wave to spawn
+const TICKS: usize = 600;
+
+#[test]
+fn plays_a_run_headless() {
+    let mut app = common::sim_app(RunSeed(1));
+    common::set_state(&mut app, AppState::InGame);
+    assert_eq!(common::state(&app), AppState::InGame);
+
+    let mut enemies_seen = false;
+    for tick in 0..TICKS {
+        let angle = tick as f32 * 0.1;
+        let mut input = app.world_mut().resource_mut::<PlayerInput>();
+        input.fire = Some(Vec2::from_angle(angle));
+        input.upgrade = Some(0);
+        app.update();
+
+        let enemies = app
+            .world_mut()
+            .query_filtered::<(), With<Enemy>>()
+            .iter(app.world())
+            .count();
+        enemies_seen |= enemies > 0;
+    }
+    assert!(enemies_seen, "no wave spawned in {} ticks", TICKS);
+
+    let players = app
+        .world_mut()
+        .query_filtered::<(), With<Player>>()
+        .iter(app.world())
+        .count();
+    assert_eq!(players, 1);
+
+    common::set_state(&mut app, AppState::MainMenu);
+    let score = app.world().resource::<Score>().current_score;
+    let replay = app
+        .world()
+        .resource::<LastReplay>()
+        .0
+        .clone()
+        .expect("the run was not recorded");
+    assert_eq!(replay.seed, RunSeed(1));
+    assert_eq!(replay.score, score);
+    // the update entering the game already runs the first tick
+    assert_eq!(replay.tick_count(), TICKS + 1);
+}